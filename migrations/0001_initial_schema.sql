@@ -0,0 +1,77 @@
+-- Users registered through the WebAuthn ceremonies
+CREATE TABLE IF NOT EXISTS users (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    email VARCHAR(255) NOT NULL,
+    display_name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id),
+    UNIQUE KEY uq_users_email (email)
+);
+
+-- Serialized webauthn-rs `Passkey` for each user
+CREATE TABLE IF NOT EXISTS user_credentials (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    user_id BIGINT NOT NULL,
+    credential_id VARBINARY(1024) NOT NULL,
+    passkey LONGTEXT NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE KEY uq_user_credentials_user (user_id),
+    CONSTRAINT fk_user_credentials_user FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- In-flight `PasskeyAuthentication` state between /login/start and /login/finish
+CREATE TABLE IF NOT EXISTS passkey_auth_state (
+    user_id BIGINT NOT NULL,
+    state LONGTEXT NOT NULL,
+    PRIMARY KEY (user_id),
+    CONSTRAINT fk_passkey_auth_state_user FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- In-flight `PasskeyRegistration` state between /register/start and /register/finish
+CREATE TABLE IF NOT EXISTS user_passkey_registrations (
+    user_id BIGINT NOT NULL,
+    registration_data LONGTEXT NOT NULL,
+    PRIMARY KEY (user_id),
+    CONSTRAINT fk_user_passkey_registrations_user FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS polls (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    title VARCHAR(255) NOT NULL,
+    description TEXT NULL,
+    creator_email VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NULL DEFAULT CURRENT_TIMESTAMP,
+    closed TINYINT(1) NULL DEFAULT 0,
+    PRIMARY KEY (id),
+    KEY idx_polls_creator (creator_email),
+    KEY idx_polls_closed_created (closed, created_at)
+);
+
+CREATE TABLE IF NOT EXISTS questions (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    poll_id BIGINT NOT NULL,
+    question_text TEXT NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT fk_questions_poll FOREIGN KEY (poll_id) REFERENCES polls (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS poll_options (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    question_id BIGINT NOT NULL,
+    option_text VARCHAR(1024) NOT NULL,
+    score BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (id),
+    CONSTRAINT fk_poll_options_question FOREIGN KEY (question_id) REFERENCES questions (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS votes (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    question_id BIGINT NOT NULL,
+    option_id BIGINT NOT NULL,
+    user_email VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id),
+    KEY idx_votes_user_question (user_email, question_id),
+    CONSTRAINT fk_votes_question FOREIGN KEY (question_id) REFERENCES questions (id) ON DELETE CASCADE,
+    CONSTRAINT fk_votes_option FOREIGN KEY (option_id) REFERENCES poll_options (id) ON DELETE CASCADE
+);
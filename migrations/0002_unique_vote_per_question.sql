@@ -0,0 +1,18 @@
+-- A user may hold at most one vote per question; the vote path relies on this
+-- constraint instead of racing SELECT COUNT checks.
+
+-- The old racy path could record a user twice on one question. Keep each
+-- user's first vote and drop the rest so the key can be added.
+DELETE extra
+FROM votes extra
+INNER JOIN votes kept
+    ON kept.question_id = extra.question_id
+    AND kept.user_email = extra.user_email
+    AND kept.id < extra.id;
+
+-- Scores counted the dropped votes too; recount them from the votes left
+UPDATE poll_options po
+SET po.score = (SELECT COUNT(*) FROM votes v WHERE v.option_id = po.id);
+
+ALTER TABLE votes
+    ADD CONSTRAINT uq_votes_question_user UNIQUE (question_id, user_email);
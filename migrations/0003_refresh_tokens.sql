@@ -0,0 +1,17 @@
+-- Opaque refresh tokens, stored as SHA-256 hashes. Every token of one login
+-- session shares a family_id (the access token `jti`); presenting a token that
+-- was already rotated revokes the whole family.
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    token_hash CHAR(64) NOT NULL,
+    user_email VARCHAR(255) NOT NULL,
+    family_id CHAR(36) NOT NULL,
+    expires_at DATETIME NOT NULL,
+    used_at DATETIME NULL,
+    revoked_at DATETIME NULL,
+    created_at TIMESTAMP NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id),
+    UNIQUE KEY uq_refresh_tokens_hash (token_hash),
+    KEY idx_refresh_tokens_family (family_id),
+    KEY idx_refresh_tokens_user (user_email)
+);
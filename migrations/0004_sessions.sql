@@ -0,0 +1,13 @@
+-- One row per login. The id is the `jti` carried by every access token of the
+-- session and the family_id of its refresh tokens; setting revoked_at kills both.
+CREATE TABLE IF NOT EXISTS sessions (
+    id CHAR(36) NOT NULL,
+    user_email VARCHAR(255) NOT NULL,
+    device VARCHAR(512) NULL,
+    ip_address VARCHAR(64) NULL,
+    created_at TIMESTAMP NULL DEFAULT CURRENT_TIMESTAMP,
+    last_seen_at DATETIME NULL,
+    revoked_at DATETIME NULL,
+    PRIMARY KEY (id),
+    KEY idx_sessions_user (user_email)
+);
@@ -0,0 +1,9 @@
+-- `jti` of every vote token that has been spent; the primary key turns a
+-- replayed token into a duplicate-key error inside the vote transaction.
+CREATE TABLE IF NOT EXISTS used_vote_tokens (
+    jti CHAR(36) NOT NULL,
+    user_email VARCHAR(255) NOT NULL,
+    poll_id BIGINT NOT NULL,
+    used_at TIMESTAMP NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (jti)
+);
@@ -0,0 +1,21 @@
+-- Allow several passkeys per account. The foreign key needs an index on
+-- user_id, so add a plain one before dropping the unique constraint.
+ALTER TABLE user_credentials
+    ADD KEY idx_user_credentials_user (user_id);
+
+ALTER TABLE user_credentials
+    DROP INDEX uq_user_credentials_user,
+    ADD CONSTRAINT uq_user_credentials_credential UNIQUE (credential_id),
+    ADD COLUMN nickname VARCHAR(100) NULL,
+    ADD COLUMN created_at TIMESTAMP NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN last_used_at DATETIME NULL;
+
+-- Stable WebAuthn user handle, reused for every passkey of the account
+ALTER TABLE users
+    ADD COLUMN user_handle CHAR(36) NULL;
+
+UPDATE users SET user_handle = UUID() WHERE user_handle IS NULL;
+
+ALTER TABLE users
+    MODIFY user_handle CHAR(36) NOT NULL,
+    ADD CONSTRAINT uq_users_user_handle UNIQUE (user_handle);
@@ -0,0 +1,8 @@
+-- Authenticator state written back after every successful authentication.
+-- counter_regressed_at marks a passkey whose signature counter went backwards,
+-- a sign that the authenticator may have been cloned.
+ALTER TABLE user_credentials
+    ADD COLUMN sign_count INT UNSIGNED NOT NULL DEFAULT 0,
+    ADD COLUMN backup_eligible TINYINT(1) NOT NULL DEFAULT 0,
+    ADD COLUMN backup_state TINYINT(1) NOT NULL DEFAULT 0,
+    ADD COLUMN counter_regressed_at DATETIME NULL;
@@ -0,0 +1,19 @@
+-- WebAuthn ceremony state keyed by a random id handed to the client instead of
+-- by user. Rows expire and are deleted when the ceremony completes, so a
+-- challenge can only be answered once. user_id is NULL for ceremonies that
+-- don't know the user up front (discoverable login).
+CREATE TABLE IF NOT EXISTS webauthn_ceremonies (
+    id CHAR(36) NOT NULL,
+    kind VARCHAR(20) NOT NULL,
+    user_id BIGINT NULL,
+    state LONGTEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    expires_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    KEY idx_webauthn_ceremonies_expires (expires_at),
+    CONSTRAINT fk_webauthn_ceremonies_user FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Replaced by webauthn_ceremonies; in-flight ceremonies are simply restarted
+DROP TABLE IF EXISTS passkey_auth_state;
+DROP TABLE IF EXISTS user_passkey_registrations;
@@ -0,0 +1,25 @@
+-- One-time recovery codes; only the SHA-256 of each code is stored
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    user_id BIGINT NOT NULL,
+    code_hash CHAR(64) NOT NULL,
+    created_at DATETIME NOT NULL,
+    used_at DATETIME NULL,
+    PRIMARY KEY (id),
+    UNIQUE KEY uq_recovery_codes_user_code (user_id, code_hash),
+    CONSTRAINT fk_recovery_codes_user FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Audit trail of every recovery. The id is the `jti` of the recovery token;
+-- completed_at is set once that token has been used to enroll a passkey.
+CREATE TABLE IF NOT EXISTS account_recoveries (
+    id CHAR(36) NOT NULL,
+    user_email VARCHAR(255) NOT NULL,
+    recovery_code_id BIGINT NOT NULL,
+    device VARCHAR(512) NULL,
+    ip_address VARCHAR(64) NULL,
+    created_at DATETIME NOT NULL,
+    completed_at DATETIME NULL,
+    PRIMARY KEY (id),
+    KEY idx_account_recoveries_user (user_email)
+);
@@ -0,0 +1,26 @@
+-- Site wide roles such as `admin`, granted per user
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_email VARCHAR(255) NOT NULL,
+    role VARCHAR(20) NOT NULL,
+    granted_by VARCHAR(255) NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (user_email, role)
+);
+
+-- Each user's role on a poll: owner, editor, voter or viewer. Users without a
+-- row are voters.
+CREATE TABLE IF NOT EXISTS poll_roles (
+    poll_id BIGINT NOT NULL,
+    user_email VARCHAR(255) NOT NULL,
+    role VARCHAR(20) NOT NULL,
+    granted_by VARCHAR(255) NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (poll_id, user_email),
+    KEY idx_poll_roles_user (user_email),
+    CONSTRAINT fk_poll_roles_poll FOREIGN KEY (poll_id) REFERENCES polls (id) ON DELETE CASCADE
+);
+
+-- Existing polls are owned by their creator
+INSERT IGNORE INTO poll_roles (poll_id, user_email, role, created_at)
+SELECT id, creator_email, 'owner', UTC_TIMESTAMP()
+FROM polls;
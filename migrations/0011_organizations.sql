@@ -0,0 +1,41 @@
+CREATE TABLE IF NOT EXISTS organizations (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    name VARCHAR(100) NOT NULL,
+    created_by VARCHAR(255) NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id)
+);
+
+-- role is owner, admin or member
+CREATE TABLE IF NOT EXISTS organization_members (
+    organization_id BIGINT NOT NULL,
+    user_email VARCHAR(255) NOT NULL,
+    role VARCHAR(20) NOT NULL,
+    joined_at DATETIME NOT NULL,
+    PRIMARY KEY (organization_id, user_email),
+    KEY idx_organization_members_user (user_email),
+    CONSTRAINT fk_organization_members_org FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE
+);
+
+-- Only the SHA-256 of the invitation token is stored, like refresh tokens
+CREATE TABLE IF NOT EXISTS organization_invitations (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    organization_id BIGINT NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    role VARCHAR(20) NOT NULL,
+    token_hash CHAR(64) NOT NULL,
+    invited_by VARCHAR(255) NOT NULL,
+    created_at DATETIME NOT NULL,
+    expires_at DATETIME NOT NULL,
+    accepted_at DATETIME NULL,
+    PRIMARY KEY (id),
+    UNIQUE KEY uq_organization_invitations_token (token_hash),
+    KEY idx_organization_invitations_org (organization_id),
+    CONSTRAINT fk_organization_invitations_org FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE
+);
+
+-- Polls that belong to an organization are only visible to its members
+ALTER TABLE polls
+    ADD COLUMN organization_id BIGINT NULL,
+    ADD KEY idx_polls_organization (organization_id),
+    ADD CONSTRAINT fk_polls_organization FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE;
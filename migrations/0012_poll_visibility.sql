@@ -0,0 +1,16 @@
+-- public: listed and open to everyone
+-- unlisted: not listed; reachable with the poll's secret share link
+-- private: only users with a role on the poll
+-- invite_only: users on the poll's invite list
+ALTER TABLE polls
+    ADD COLUMN visibility VARCHAR(20) NOT NULL DEFAULT 'public',
+    ADD COLUMN share_token_hash CHAR(64) NULL;
+
+CREATE TABLE IF NOT EXISTS poll_invites (
+    poll_id BIGINT NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    invited_by VARCHAR(255) NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (poll_id, email),
+    CONSTRAINT fk_poll_invites_poll FOREIGN KEY (poll_id) REFERENCES polls (id) ON DELETE CASCADE
+);
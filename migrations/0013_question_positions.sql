@@ -0,0 +1,8 @@
+-- Display order of questions within a poll and options within a question, so
+-- they can be reordered after creation. Existing rows keep their insertion order.
+ALTER TABLE questions ADD COLUMN position INT NOT NULL DEFAULT 0;
+ALTER TABLE poll_options ADD COLUMN position INT NOT NULL DEFAULT 0;
+UPDATE questions SET position = id;
+UPDATE poll_options SET position = id;
+
+ALTER TABLE polls ADD COLUMN updated_at DATETIME NULL;
@@ -0,0 +1,2 @@
+-- Archived polls are closed, hidden from listings and can be restored by their owner
+ALTER TABLE polls ADD COLUMN archived_at DATETIME NULL;
@@ -0,0 +1,29 @@
+-- question_type is single, multi, ranked, rating or free_text.
+-- min/max_selections bound multi and ranked ballots, scale_min/max rating answers.
+ALTER TABLE questions
+    ADD COLUMN question_type VARCHAR(20) NOT NULL DEFAULT 'single',
+    ADD COLUMN min_selections INT NULL,
+    ADD COLUMN max_selections INT NULL,
+    ADD COLUMN scale_min INT NULL,
+    ADD COLUMN scale_max INT NULL;
+
+-- One row per ballot and question. option_id is only set for single choice;
+-- rating and free_text answers have no options.
+ALTER TABLE votes
+    MODIFY option_id BIGINT NULL,
+    ADD COLUMN rating INT NULL,
+    ADD COLUMN answer_text TEXT NULL;
+
+-- Options picked on a ballot; `rank` is the preference (1 = first) on ranked ballots
+CREATE TABLE IF NOT EXISTS vote_selections (
+    vote_id BIGINT NOT NULL,
+    option_id BIGINT NOT NULL,
+    `rank` INT NULL,
+    PRIMARY KEY (vote_id, option_id),
+    KEY idx_vote_selections_option (option_id),
+    CONSTRAINT fk_vote_selections_vote FOREIGN KEY (vote_id) REFERENCES votes (id) ON DELETE CASCADE,
+    CONSTRAINT fk_vote_selections_option FOREIGN KEY (option_id) REFERENCES poll_options (id) ON DELETE CASCADE
+);
+
+INSERT INTO vote_selections (vote_id, option_id)
+SELECT id, option_id FROM votes WHERE option_id IS NOT NULL;
@@ -0,0 +1,19 @@
+-- Opt-in per poll: voters may replace or withdraw their ballot until the poll closes
+ALTER TABLE polls ADD COLUMN allow_vote_changes TINYINT(1) NOT NULL DEFAULT 0;
+
+ALTER TABLE votes ADD COLUMN updated_at DATETIME NULL;
+
+-- Every ballot cast, replaced or withdrawn. The votes table only holds the
+-- latest ballot; this keeps the earlier ones for audit.
+-- action is cast, change or retract; ballot is the JSON ballot (NULL on retract).
+CREATE TABLE IF NOT EXISTS vote_history (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    question_id BIGINT NOT NULL,
+    user_email VARCHAR(255) NOT NULL,
+    action VARCHAR(20) NOT NULL,
+    ballot TEXT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    KEY idx_vote_history_question_user (question_id, user_email),
+    CONSTRAINT fk_vote_history_question FOREIGN KEY (question_id) REFERENCES questions (id) ON DELETE CASCADE
+);
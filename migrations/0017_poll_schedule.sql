@@ -0,0 +1,6 @@
+-- Optional voting window in UTC. Before opens_at votes are refused; at closes_at
+-- the scheduler closes the poll.
+ALTER TABLE polls
+    ADD COLUMN opens_at DATETIME NULL,
+    ADD COLUMN closes_at DATETIME NULL,
+    ADD KEY idx_polls_closes_at (closed, closes_at);
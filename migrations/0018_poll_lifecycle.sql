@@ -0,0 +1,45 @@
+-- Explicit lifecycle: draft, scheduled, open, paused, closed or archived.
+-- status replaces the closed flag and archived_at: both are backfilled into it
+-- and dropped below.
+ALTER TABLE polls
+    ADD COLUMN status VARCHAR(20) NOT NULL DEFAULT 'open',
+    ADD KEY idx_polls_status (status, opens_at);
+
+UPDATE polls
+SET status = CASE
+    WHEN archived_at IS NOT NULL THEN 'archived'
+    WHEN closed = TRUE THEN 'closed'
+    WHEN opens_at IS NOT NULL AND opens_at > UTC_TIMESTAMP() THEN 'scheduled'
+    ELSE 'open'
+END;
+
+-- Every status change with who made it; changed_by is NULL for the scheduler
+-- and for archives carried over below, from_status is NULL for the status a
+-- poll was created in
+CREATE TABLE IF NOT EXISTS poll_status_history (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    poll_id BIGINT NOT NULL,
+    from_status VARCHAR(20) NULL,
+    to_status VARCHAR(20) NOT NULL,
+    changed_by VARCHAR(255) NULL,
+    reason VARCHAR(500) NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    KEY idx_poll_status_history_poll (poll_id),
+    CONSTRAINT fk_poll_status_history_poll FOREIGN KEY (poll_id) REFERENCES polls (id) ON DELETE CASCADE
+);
+
+-- The history is where archived_at is read from now, so carry over when
+-- already archived polls were archived
+INSERT INTO poll_status_history (poll_id, from_status, to_status, changed_by, reason, created_at)
+SELECT id, 'closed', 'archived', NULL, 'Archived before the status history was kept.', archived_at
+FROM polls
+WHERE archived_at IS NOT NULL;
+
+ALTER TABLE polls
+    DROP KEY idx_polls_closed_created,
+    DROP KEY idx_polls_closes_at,
+    DROP COLUMN closed,
+    DROP COLUMN archived_at,
+    ADD KEY idx_polls_status_created (status, created_at),
+    ADD KEY idx_polls_status_closes_at (status, closes_at);
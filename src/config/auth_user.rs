@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, http::header::AUTHORIZATION, web, FromRequest, HttpMessage, HttpRequest};
+use futures::future::LocalBoxFuture;
+use sqlx::MySqlPool;
+
+use super::{verify_access_token, Claims, Settings};
+use crate::errors::AppError;
+
+// The caller authenticated by `jwt_middleware`. Only usable on routes wrapped
+// by the middleware; elsewhere extraction fails with 401.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub email: String,
+    pub roles: Vec<String>,
+    pub session_id: Option<String>,
+}
+
+impl From<Claims> for AuthUser {
+    fn from(claims: Claims) -> Self {
+        AuthUser {
+            email: claims.sub,
+            roles: claims.roles,
+            session_id: claims.jti,
+        }
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user = req
+            .extensions()
+            .get::<Claims>()
+            .cloned()
+            .map(AuthUser::from)
+            .ok_or_else(|| AppError::unauthorized("unauthenticated", "Authentication required."));
+        ready(user)
+    }
+}
+
+// The caller on routes outside the JWT scope, where signing in is optional.
+// The access token may come as a bearer header or, for websockets which can't
+// set headers, as `?access_token=`. A token that is present but invalid is
+// still rejected rather than treated as anonymous.
+#[derive(Debug, Clone)]
+pub struct MaybeAuthUser(pub Option<AuthUser>);
+
+impl FromRequest for MaybeAuthUser {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Some(claims) = req.extensions().get::<Claims>().cloned() {
+            return Box::pin(ready(Ok(MaybeAuthUser(Some(claims.into())))));
+        }
+
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|auth_header| auth_header.to_str().ok())
+            .and_then(|auth_str| auth_str.strip_prefix("Bearer "))
+            .map(str::to_owned)
+            .or_else(|| {
+                web::Query::<HashMap<String, String>>::from_query(req.query_string())
+                    .ok()
+                    .and_then(|query| query.get("access_token").cloned())
+            });
+        let settings = req.app_data::<web::Data<Settings>>().cloned();
+        let pool = req.app_data::<web::Data<MySqlPool>>().cloned();
+
+        Box::pin(async move {
+            let token = match token {
+                Some(token) => token,
+                None => return Ok(MaybeAuthUser(None)),
+            };
+            let settings = settings
+                .ok_or_else(|| AppError::internal("Settings are not registered as app data"))?;
+            let pool = pool
+                .ok_or_else(|| AppError::internal("Database pool is not registered as app data"))?;
+
+            let claims = verify_access_token(&token, &settings, &pool).await?;
+            Ok(MaybeAuthUser(Some(claims.into())))
+        })
+    }
+}
@@ -1,52 +1,222 @@
-use std::env;
-
-use actix_web::{body::MessageBody, dev::{ServiceRequest, ServiceResponse}, http::header::AUTHORIZATION, middleware::Next, HttpMessage};
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use actix_web::{body::MessageBody, web::Data, dev::{ServiceRequest, ServiceResponse}, http::header::{HeaderName, AUTHORIZATION}, middleware::Next, HttpMessage};
+use jsonwebtoken::{decode, encode, get_current_timestamp, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+use uuid::Uuid;
+
+use super::{JwtSettings, Settings};
+use crate::errors::AppError;
+use crate::polls::Ballot;
+use crate::touch_session;
 
 
 // jwt middleware
 #[derive(Debug, Deserialize, Serialize, Clone)]
-struct Claims {
-    sub: String,
-    exp: usize,
-    // Add other fields as needed
+pub struct Claims {
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub iss: String,
+    pub aud: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    // Identifies the login session the token was issued for
+    #[serde(default)]
+    pub jti: Option<String>,
+}
+
+// Sign a short lived access token for `email` in login session `session_id`
+pub fn issue_access_token(
+    settings: &JwtSettings,
+    email: &str,
+    roles: Vec<String>,
+    session_id: &str,
+) -> Result<String, AppError> {
+    let now = get_current_timestamp() as usize;
+    let claims = Claims {
+        sub: email.to_owned(),
+        exp: now + settings.access_token_ttl_secs as usize,
+        iat: now,
+        iss: settings.issuer.clone(),
+        aud: settings.audience.clone(),
+        roles,
+        jti: Some(session_id.to_owned()),
+    };
+    encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(settings.secret.as_ref()),
+    )
+    .map_err(|err| AppError::internal(format!("Failed to encode token: {}", err)))
+}
+
+// Verify signature, expiry, issuer and audience of an access token
+pub fn decode_access_token(settings: &JwtSettings, token: &str) -> Result<Claims, AppError> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_issuer(&[&settings.issuer]);
+    validation.set_audience(&[&settings.audience]);
+    validation.set_required_spec_claims(&["exp", "sub", "iss", "aud"]);
+
+    decode::<Claims>(token, &DecodingKey::from_secret(settings.secret.as_ref()), &validation)
+        .map(|token_data| token_data.claims)
+        .map_err(|_| AppError::unauthorized("invalid_token", "Invalid token."))
+}
+
+// Proof of a fresh passkey verification for one ballot, minted by /getpass
+// and spent by the vote endpoint. The ballot itself travels in the token.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct VoteClaims {
+    pub sub: String,
+    pub poll_id: i64,
+    pub question_id: i64,
+    pub ballot: Ballot,
+    // Recorded when the token is used so it can't be replayed
+    pub jti: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub iss: String,
+    pub aud: String,
+}
+
+// Vote tokens get their own audience so they are never accepted as access tokens
+fn vote_audience(settings: &JwtSettings) -> String {
+    format!("{}:vote", settings.audience)
+}
+
+pub fn issue_vote_token(
+    settings: &JwtSettings,
+    email: &str,
+    poll_id: i64,
+    question_id: i64,
+    ballot: &Ballot,
+) -> Result<String, AppError> {
+    let now = get_current_timestamp() as usize;
+    let claims = VoteClaims {
+        sub: email.to_owned(),
+        poll_id,
+        question_id,
+        ballot: ballot.clone(),
+        jti: Uuid::new_v4().to_string(),
+        exp: now + settings.vote_token_ttl_secs as usize,
+        iat: now,
+        iss: settings.issuer.clone(),
+        aud: vote_audience(settings),
+    };
+    encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(settings.secret.as_ref()),
+    )
+    .map_err(|err| AppError::internal(format!("Failed to encode token: {}", err)))
+}
+
+pub fn decode_vote_token(settings: &JwtSettings, token: &str) -> Result<VoteClaims, AppError> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_issuer(&[&settings.issuer]);
+    validation.set_audience(&[vote_audience(settings)]);
+    validation.set_required_spec_claims(&["exp", "sub", "iss", "aud"]);
+
+    decode::<VoteClaims>(token, &DecodingKey::from_secret(settings.secret.as_ref()), &validation)
+        .map(|token_data| token_data.claims)
+        .map_err(|_| AppError::unauthorized("invalid_vote_token", "Invalid or expired vote token."))
+}
+
+// Issued by /recover after a recovery code is spent. It is only accepted by the
+// recovery enrollment routes, never as an access token.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RecoveryClaims {
+    pub sub: String,
+    // Id of the audit row in account_recoveries
+    pub jti: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub iss: String,
+    pub aud: String,
+}
+
+fn recovery_audience(settings: &JwtSettings) -> String {
+    format!("{}:recovery", settings.audience)
+}
+
+pub fn issue_recovery_token(
+    settings: &JwtSettings,
+    email: &str,
+    recovery_id: &str,
+) -> Result<String, AppError> {
+    let now = get_current_timestamp() as usize;
+    let claims = RecoveryClaims {
+        sub: email.to_owned(),
+        jti: recovery_id.to_owned(),
+        exp: now + settings.recovery_token_ttl_secs as usize,
+        iat: now,
+        iss: settings.issuer.clone(),
+        aud: recovery_audience(settings),
+    };
+    encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(settings.secret.as_ref()),
+    )
+    .map_err(|err| AppError::internal(format!("Failed to encode token: {}", err)))
 }
+
+pub fn decode_recovery_token(settings: &JwtSettings, token: &str) -> Result<RecoveryClaims, AppError> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_issuer(&[&settings.issuer]);
+    validation.set_audience(&[recovery_audience(settings)]);
+    validation.set_required_spec_claims(&["exp", "sub", "iss", "aud"]);
+
+    decode::<RecoveryClaims>(token, &DecodingKey::from_secret(settings.secret.as_ref()), &validation)
+        .map(|token_data| token_data.claims)
+        .map_err(|_| {
+            AppError::unauthorized("invalid_recovery_token", "Invalid or expired recovery token.")
+        })
+}
+
+// Decode an access token and make sure its login session is still active
+pub async fn verify_access_token(
+    token: &str,
+    settings: &Settings,
+    pool: &MySqlPool,
+) -> Result<Claims, AppError> {
+    let claims = decode_access_token(&settings.jwt, token)?;
+
+    // A valid signature is not enough: the session must not have been revoked
+    let session_id = claims
+        .jti
+        .as_deref()
+        .ok_or_else(|| AppError::unauthorized("invalid_token", "Token has no session."))?;
+    if !touch_session(session_id, &claims.sub, pool).await? {
+        return Err(AppError::unauthorized("session_revoked", "Session has been revoked."));
+    }
+    Ok(claims)
+}
+
 pub async fn jwt_middleware(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
     println!("JWT middleware called");
-    let headers = req.headers();
-    if let Some(auth_header) = headers.get(AUTHORIZATION) {
-        if let Ok(auth_str) = auth_header.to_str() {
-            if auth_str.starts_with("Bearer ") {
-                let token = &auth_str[7..];
-                let secret_key = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-                let decoding_key = DecodingKey::from_secret(secret_key.as_ref());
-                let validation = Validation::new(Algorithm::HS256);
-
-                match decode::<Claims>(token, &decoding_key, &validation) {
-                    Ok(token_data) => {
-                        let claims = token_data.claims.clone();
-                        req.extensions_mut().insert(claims.clone());
-                        req.headers_mut().insert(
-                            actix_web::http::header::HeaderName::from_static("user_id"),
-                            claims.sub.parse().unwrap(),
-                        );
-                        println!("user_id in req header: {:?}", req.headers().get("user_id"));
-                        return next.call(req).await;
-                    }
-                    Err(_) => {
-                        return Err(actix_web::error::ErrorUnauthorized("Invalid token"));
-                    }
-                }
-            }
-        }
-    } else {
-        return Err(actix_web::error::ErrorUnauthorized("No token provided"));
-    }
-    next.call(req).await
-}
+    // Identity only ever comes from the verified token, never from a client supplied header
+    req.headers_mut().remove(HeaderName::from_static("user_id"));
+
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|auth_header| auth_header.to_str().ok())
+        .and_then(|auth_str| auth_str.strip_prefix("Bearer "))
+        .map(str::to_owned)
+        .ok_or_else(|| AppError::unauthorized("missing_token", "No token provided."))?;
 
+    let settings = req
+        .app_data::<Data<Settings>>()
+        .ok_or_else(|| AppError::internal("Settings are not registered as app data"))?;
+    let pool = req
+        .app_data::<Data<MySqlPool>>()
+        .ok_or_else(|| AppError::internal("Database pool is not registered as app data"))?;
+    let claims = verify_access_token(&token, settings, pool).await?;
 
+    // Handlers read these back through the `AuthUser` extractor
+    req.extensions_mut().insert(claims);
+    next.call(req).await
+}
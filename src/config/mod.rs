@@ -1,20 +1,31 @@
-use dotenv::dotenv;
 use serde::{Deserialize, Serialize};
 use sqlx::MySqlPool;
 use std::env;
 use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::Path;
+pub mod auth_user;
 pub mod jwt_middleware;
+pub mod settings;
 
-pub async fn database_connection() -> Result<MySqlPool, sqlx::Error> {
-    dotenv().ok();
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
-    let pool = MySqlPool::connect(&database_url).await?;
-    println!("Connected to database {}", &database_url);
+pub async fn database_connection(settings: &DatabaseSettings) -> Result<MySqlPool, sqlx::Error> {
+    let pool = MySqlPool::connect(&settings.url).await?;
+    println!("Connected to database");
+
+    // Bring the schema up to date before any handler touches it
+    run_migrations(&pool).await?;
     Ok(pool)
 }
 
+// Apply the versioned migrations embedded from ./migrations at compile time
+pub async fn run_migrations(pool: &MySqlPool) -> Result<(), sqlx::Error> {
+    sqlx::migrate!("./migrations").run(pool).await?;
+    println!("Database migrations applied");
+    Ok(())
+}
+
 pub mod webauth_utilities;
 pub use webauth_utilities::*;
-pub use jwt_middleware::*;
\ No newline at end of file
+pub use auth_user::*;
+pub use settings::*;
+pub use jwt_middleware::*;
@@ -0,0 +1,271 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use dotenv::dotenv;
+use reqwest::Url;
+use serde::Deserialize;
+
+use super::WebauthnSettings;
+
+// Used when APP_CONFIG is not set; a missing default file is not an error
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+// Typed server configuration. Loaded once in `main` from an optional TOML file,
+// then overridden by environment variables, then validated.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub jwt: JwtSettings,
+    pub webauthn: WebauthnSettings,
+    pub access: AccessSettings,
+    pub scheduler: SchedulerSettings,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            host: "0.0.0.0".to_string(),
+            port: 3001,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DatabaseSettings {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct JwtSettings {
+    pub secret: String,
+    pub issuer: String,
+    pub audience: String,
+    // Access tokens are short lived; clients renew them with a refresh token
+    pub access_token_ttl_secs: u64,
+    pub refresh_token_ttl_secs: u64,
+    // Vote tokens only bridge passkey verification and the vote request
+    pub vote_token_ttl_secs: u64,
+    // Recovery tokens only allow enrolling a new passkey after a recovery code
+    pub recovery_token_ttl_secs: u64,
+}
+
+impl Default for JwtSettings {
+    fn default() -> Self {
+        JwtSettings {
+            secret: String::new(),
+            issuer: "polling-site".to_string(),
+            audience: "polling-site-web".to_string(),
+            access_token_ttl_secs: 15 * 60,
+            refresh_token_ttl_secs: 30 * 24 * 60 * 60,
+            vote_token_ttl_secs: 5 * 60,
+            recovery_token_ttl_secs: 10 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AccessSettings {
+    // Granted the site wide admin role at startup
+    pub admin_emails: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SchedulerSettings {
+    // How often polls are checked for their opens_at and closes_at
+    pub interval_secs: u64,
+}
+
+impl Default for SchedulerSettings {
+    fn default() -> Self {
+        SchedulerSettings { interval_secs: 15 }
+    }
+}
+
+// Every problem found while loading, reported together
+#[derive(Debug)]
+pub struct SettingsError(pub Vec<String>);
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for problem in &self.0 {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl Settings {
+    pub fn load() -> Result<Settings, SettingsError> {
+        dotenv().ok();
+
+        let mut settings = match env::var("APP_CONFIG") {
+            Ok(path) => Settings::from_file(&path)?,
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Settings::from_file(DEFAULT_CONFIG_PATH)?
+            }
+            Err(_) => Settings::default(),
+        };
+
+        let mut problems = settings.apply_env();
+        problems.extend(settings.validate());
+        if problems.is_empty() {
+            Ok(settings)
+        } else {
+            Err(SettingsError(problems))
+        }
+    }
+
+    fn from_file(path: &str) -> Result<Settings, SettingsError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| SettingsError(vec![format!("cannot read {}: {}", path, err)]))?;
+        toml::from_str(&contents)
+            .map_err(|err| SettingsError(vec![format!("cannot parse {}: {}", path, err)]))
+    }
+
+    // Environment variables win over the file so deployments can inject secrets
+    fn apply_env(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Ok(host) = env::var("SERVER_HOST") {
+            self.server.host = host;
+        }
+        if let Ok(port) = env::var("SERVER_PORT") {
+            match port.parse() {
+                Ok(port) => self.server.port = port,
+                Err(_) => problems.push(format!("SERVER_PORT must be a port number, got {:?}", port)),
+            }
+        }
+        if let Ok(url) = env::var("DATABASE_URL") {
+            self.database.url = url;
+        }
+        if let Ok(secret) = env::var("JWT_SECRET") {
+            self.jwt.secret = secret;
+        }
+        if let Ok(issuer) = env::var("JWT_ISSUER") {
+            self.jwt.issuer = issuer;
+        }
+        if let Ok(audience) = env::var("JWT_AUDIENCE") {
+            self.jwt.audience = audience;
+        }
+        if let Ok(ttl) = env::var("JWT_ACCESS_TTL_SECS") {
+            match ttl.parse() {
+                Ok(ttl) => self.jwt.access_token_ttl_secs = ttl,
+                Err(_) => problems.push(format!("JWT_ACCESS_TTL_SECS must be a number, got {:?}", ttl)),
+            }
+        }
+        if let Ok(ttl) = env::var("JWT_REFRESH_TTL_SECS") {
+            match ttl.parse() {
+                Ok(ttl) => self.jwt.refresh_token_ttl_secs = ttl,
+                Err(_) => problems.push(format!("JWT_REFRESH_TTL_SECS must be a number, got {:?}", ttl)),
+            }
+        }
+        if let Ok(ttl) = env::var("JWT_VOTE_TTL_SECS") {
+            match ttl.parse() {
+                Ok(ttl) => self.jwt.vote_token_ttl_secs = ttl,
+                Err(_) => problems.push(format!("JWT_VOTE_TTL_SECS must be a number, got {:?}", ttl)),
+            }
+        }
+        if let Ok(ttl) = env::var("JWT_RECOVERY_TTL_SECS") {
+            match ttl.parse() {
+                Ok(ttl) => self.jwt.recovery_token_ttl_secs = ttl,
+                Err(_) => problems.push(format!("JWT_RECOVERY_TTL_SECS must be a number, got {:?}", ttl)),
+            }
+        }
+        if let Ok(emails) = env::var("ADMIN_EMAILS") {
+            self.access.admin_emails = emails
+                .split(',')
+                .map(|email| email.trim().to_string())
+                .filter(|email| !email.is_empty())
+                .collect();
+        }
+        if let Ok(interval) = env::var("POLL_SCHEDULER_INTERVAL_SECS") {
+            match interval.parse() {
+                Ok(interval) => self.scheduler.interval_secs = interval,
+                Err(_) => problems.push(format!(
+                    "POLL_SCHEDULER_INTERVAL_SECS must be a number, got {:?}",
+                    interval
+                )),
+            }
+        }
+        if let Err(problem) = self.webauthn.apply_env() {
+            problems.push(problem);
+        }
+
+        problems
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.server.host.trim().is_empty() {
+            problems.push("server.host (SERVER_HOST) must not be empty".to_string());
+        }
+        if self.database.url.trim().is_empty() {
+            problems.push("database.url (DATABASE_URL) must be set".to_string());
+        }
+        if self.jwt.secret.len() < 32 {
+            problems.push("jwt.secret (JWT_SECRET) must be at least 32 characters".to_string());
+        }
+        if self.jwt.issuer.trim().is_empty() || self.jwt.audience.trim().is_empty() {
+            problems.push("jwt.issuer and jwt.audience must not be empty".to_string());
+        }
+        if self.jwt.access_token_ttl_secs == 0 {
+            problems.push("jwt.access_token_ttl_secs must be greater than zero".to_string());
+        }
+        if self.jwt.refresh_token_ttl_secs <= self.jwt.access_token_ttl_secs {
+            problems.push(
+                "jwt.refresh_token_ttl_secs must be longer than jwt.access_token_ttl_secs".to_string(),
+            );
+        }
+        if self.jwt.vote_token_ttl_secs == 0 || self.jwt.vote_token_ttl_secs > 15 * 60 {
+            problems.push("jwt.vote_token_ttl_secs must be between 1 and 900 seconds".to_string());
+        }
+        if self.jwt.recovery_token_ttl_secs == 0 || self.jwt.recovery_token_ttl_secs > 60 * 60 {
+            problems.push("jwt.recovery_token_ttl_secs must be between 1 and 3600 seconds".to_string());
+        }
+        if self.webauthn.rp_id.trim().is_empty() {
+            problems.push("webauthn.rp_id (WEBAUTHN_RP_ID) must not be empty".to_string());
+        }
+        if self.webauthn.origins.is_empty() {
+            problems.push("webauthn.origins (WEBAUTHN_ORIGINS) needs at least one origin".to_string());
+        }
+        if self.webauthn.timeout_secs == 0 || self.webauthn.sweep_interval_secs == 0 {
+            problems.push(
+                "webauthn.timeout_secs and webauthn.sweep_interval_secs must be greater than zero"
+                    .to_string(),
+            );
+        }
+        if self.scheduler.interval_secs == 0 {
+            problems.push("scheduler.interval_secs must be greater than zero".to_string());
+        }
+        for origin in &self.webauthn.origins {
+            if Url::parse(origin).is_err() {
+                problems.push(format!("webauthn.origins contains an invalid URL: {:?}", origin));
+            }
+        }
+
+        problems
+    }
+
+    pub fn bind_address(&self) -> (String, u16) {
+        (self.server.host.clone(), self.server.port)
+    }
+}
@@ -1,13 +1,133 @@
+use std::env;
+use std::time::Duration;
+
 use reqwest::Url;
+use serde::Deserialize;
+use webauthn_rs::prelude::CreationChallengeResponse;
 use webauthn_rs::{Webauthn, WebauthnBuilder};
 
-// Create the WebAuthn instance
-pub fn create_webauthn_instance() -> Webauthn {
-    let rp_id = "localhost"; // Adjust for your actual RP ID
-    let rp_origin = Url::parse("http://localhost:3000").expect("Invalid URL");
+// Relying party settings; defaults match local development against the frontend on :3000
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WebauthnSettings {
+    pub rp_id: String,
+    pub rp_name: String,
+    // The first origin is the primary one, the rest are also accepted
+    pub origins: Vec<String>,
+    // Accept any subdomain of the listed origins (e.g. https://vote.example.com)
+    pub allow_subdomains: bool,
+    pub timeout_secs: u64,
+    // How often expired ceremonies and abandoned registrations are swept
+    pub sweep_interval_secs: u64,
+    // What to do when a passkey's signature counter fails to increase
+    pub counter_policy: CounterPolicy,
+}
+
+// A counter that goes backwards suggests a cloned authenticator. `Reject` fails
+// the login, `Flag` lets it through but marks the credential for review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CounterPolicy {
+    Reject,
+    Flag,
+}
+
+impl std::str::FromStr for CounterPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "reject" => Ok(CounterPolicy::Reject),
+            "flag" => Ok(CounterPolicy::Flag),
+            _ => Err(format!("WEBAUTHN_COUNTER_POLICY must be reject or flag, got {:?}", value)),
+        }
+    }
+}
+
+impl Default for WebauthnSettings {
+    fn default() -> Self {
+        WebauthnSettings {
+            rp_id: "localhost".to_string(),
+            rp_name: "Polling Site".to_string(),
+            origins: vec!["http://localhost:3000".to_string()],
+            allow_subdomains: false,
+            timeout_secs: 300,
+            sweep_interval_secs: 60,
+            counter_policy: CounterPolicy::Reject,
+        }
+    }
+}
+
+impl WebauthnSettings {
+    // Apply overrides from WEBAUTHN_RP_ID, WEBAUTHN_RP_NAME, WEBAUTHN_ORIGINS (comma separated),
+    // WEBAUTHN_ALLOW_SUBDOMAINS, WEBAUTHN_TIMEOUT_SECS, WEBAUTHN_SWEEP_INTERVAL_SECS and
+    // WEBAUTHN_COUNTER_POLICY
+    pub fn apply_env(&mut self) -> Result<(), String> {
+        if let Ok(rp_id) = env::var("WEBAUTHN_RP_ID") {
+            self.rp_id = rp_id;
+        }
+        if let Ok(rp_name) = env::var("WEBAUTHN_RP_NAME") {
+            self.rp_name = rp_name;
+        }
+        if let Ok(origins) = env::var("WEBAUTHN_ORIGINS") {
+            self.origins = origins
+                .split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect();
+        }
+        if let Ok(allow) = env::var("WEBAUTHN_ALLOW_SUBDOMAINS") {
+            self.allow_subdomains = allow
+                .parse()
+                .map_err(|_| format!("WEBAUTHN_ALLOW_SUBDOMAINS must be true or false, got {:?}", allow))?;
+        }
+        if let Ok(timeout) = env::var("WEBAUTHN_TIMEOUT_SECS") {
+            self.timeout_secs = timeout
+                .parse()
+                .map_err(|_| format!("WEBAUTHN_TIMEOUT_SECS must be a number, got {:?}", timeout))?;
+        }
+        if let Ok(interval) = env::var("WEBAUTHN_SWEEP_INTERVAL_SECS") {
+            self.sweep_interval_secs = interval.parse().map_err(|_| {
+                format!("WEBAUTHN_SWEEP_INTERVAL_SECS must be a number, got {:?}", interval)
+            })?;
+        }
+        if let Ok(policy) = env::var("WEBAUTHN_COUNTER_POLICY") {
+            self.counter_policy = policy.parse()?;
+        }
+        Ok(())
+    }
+}
+
+// Create the WebAuthn instance; built once at startup and shared through app_data
+pub fn create_webauthn_instance(settings: &WebauthnSettings) -> Result<Webauthn, String> {
+    let mut origins = settings.origins.iter().map(|origin| {
+        Url::parse(origin).map_err(|err| format!("Invalid WebAuthn origin {:?}: {}", origin, err))
+    });
+    let rp_origin = origins
+        .next()
+        .ok_or_else(|| "At least one WebAuthn origin must be configured".to_string())??;
+
+    let mut builder = WebauthnBuilder::new(&settings.rp_id, &rp_origin)
+        .map_err(|err| format!("Invalid WebAuthn configuration: {}", err))?
+        .rp_name(&settings.rp_name)
+        .allow_subdomains(settings.allow_subdomains)
+        .timeout(Duration::from_secs(settings.timeout_secs));
+    for origin in origins {
+        builder = builder.append_allowed_origin(&origin?);
+    }
+
+    builder
+        .build()
+        .map_err(|err| format!("Invalid WebAuthn configuration: {}", err))
+}
 
-    let mut builder = WebauthnBuilder::new(rp_id, &rp_origin).expect("Invalid configuration");
-    let webauthn = builder.build().expect("Invalid configuration");
-    // println!("{:?}", webauthn);
-    return webauthn;
+// Ask for a discoverable (resident) credential. Passkey registration in webauthn-rs
+// leaves that to the authenticator, and a security key that stores a
+// non-discoverable credential could never be found by usernameless login.
+pub fn require_resident_key(challenge: &mut CreationChallengeResponse) {
+    if let Some(selection) = challenge.public_key.authenticator_selection.as_mut() {
+        selection.require_resident_key = true;
+        // ResidentKeyRequirement isn't re-exported by webauthn-rs, so parse it by name
+        selection.resident_key = serde_json::from_value(serde_json::json!("required")).ok();
+    }
 }
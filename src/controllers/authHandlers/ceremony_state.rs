@@ -0,0 +1,148 @@
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+use crate::errors::AppError;
+
+// Which ceremony a stored state belongs to, so one can't be finished as another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CeremonyKind {
+    Registration,
+    Authentication,
+    Verification,
+    Discoverable,
+}
+
+impl CeremonyKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            CeremonyKind::Registration => "registration",
+            CeremonyKind::Authentication => "authentication",
+            CeremonyKind::Verification => "verification",
+            CeremonyKind::Discoverable => "discoverable",
+        }
+    }
+}
+
+// A WebAuthn challenge plus the id the client must send back to finish it.
+// Flattened so `publicKey` stays at the top level for navigator.credentials.
+#[derive(Serialize)]
+pub struct CeremonyChallenge<T: Serialize> {
+    pub ceremony_id: String,
+    #[serde(flatten)]
+    pub challenge: T,
+}
+
+// Persist ceremony state under a fresh random id that expires after `ttl_secs`
+pub async fn store_ceremony_state<T: Serialize>(
+    kind: CeremonyKind,
+    email: Option<&str>,
+    state: &T,
+    ttl_secs: u64,
+    pool: &MySqlPool,
+) -> Result<String, AppError> {
+    let state = serde_json::to_string(state).map_err(|err| {
+        AppError::internal(format!("Failed to serialize {} state: {}", kind.as_str(), err))
+    })?;
+    let ceremony_id = Uuid::new_v4().to_string();
+
+    sqlx::query(
+        r#"
+        INSERT INTO webauthn_ceremonies (id, kind, user_id, state, created_at, expires_at)
+        VALUES (
+            ?, ?,
+            (SELECT id FROM users WHERE email = ?),
+            ?,
+            UTC_TIMESTAMP(),
+            DATE_ADD(UTC_TIMESTAMP(), INTERVAL ? SECOND)
+        )
+        "#,
+    )
+    .bind(&ceremony_id)
+    .bind(kind.as_str())
+    .bind(email)
+    .bind(state)
+    .bind(ttl_secs)
+    .execute(pool)
+    .await?;
+
+    Ok(ceremony_id)
+}
+
+// Load and delete ceremony state in one step, so every challenge is single use.
+// When `email` is given the ceremony must have been started for that user.
+pub async fn take_ceremony_state<T: DeserializeOwned>(
+    ceremony_id: &str,
+    kind: CeremonyKind,
+    email: Option<&str>,
+    pool: &MySqlPool,
+) -> Result<T, AppError> {
+    let not_found = || {
+        AppError::not_found(
+            "ceremony_not_found",
+            "No ceremony in progress; it may have expired or already been used.",
+        )
+    };
+
+    let mut tx = pool.begin().await?;
+    let row = sqlx::query(
+        r#"
+        SELECT c.state, u.email, c.expires_at > UTC_TIMESTAMP() AS live
+        FROM webauthn_ceremonies c
+        LEFT JOIN users u ON u.id = c.user_id
+        WHERE c.id = ? AND c.kind = ?
+        FOR UPDATE
+        "#,
+    )
+    .bind(ceremony_id)
+    .bind(kind.as_str())
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(not_found)?;
+
+    // Consumed whatever happens next; a failed attempt must start over
+    sqlx::query("DELETE FROM webauthn_ceremonies WHERE id = ?")
+        .bind(ceremony_id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    let owner: Option<String> = row.get("email");
+    if !row.get::<bool, _>("live") || (email.is_some() && owner.as_deref() != email) {
+        return Err(not_found());
+    }
+
+    serde_json::from_str(&row.get::<String, _>("state")).map_err(|err| {
+        AppError::internal(format!("Failed to deserialize {} state: {}", kind.as_str(), err))
+    })
+}
+
+// Remove expired ceremonies, then accounts whose registration never completed:
+// no passkey, no ceremony left, and older than `abandoned_after_secs`.
+// Returns the number of ceremonies and accounts removed.
+pub async fn sweep_expired_ceremonies(
+    abandoned_after_secs: u64,
+    pool: &MySqlPool,
+) -> Result<(u64, u64), AppError> {
+    let ceremonies = sqlx::query("DELETE FROM webauthn_ceremonies WHERE expires_at <= UTC_TIMESTAMP()")
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    // users.created_at is a TIMESTAMP, read back in the session time zone just
+    // like NOW(), so the cutoff uses NOW() rather than UTC_TIMESTAMP()
+    let users = sqlx::query(
+        r#"
+        DELETE FROM users
+        WHERE created_at < NOW() - INTERVAL ? SECOND
+          AND NOT EXISTS (SELECT 1 FROM user_credentials uc WHERE uc.user_id = users.id)
+          AND NOT EXISTS (SELECT 1 FROM webauthn_ceremonies c WHERE c.user_id = users.id)
+        "#,
+    )
+    .bind(abandoned_after_secs)
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok((ceremonies, users))
+}
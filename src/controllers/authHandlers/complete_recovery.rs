@@ -0,0 +1,32 @@
+use sqlx::MySqlConnection;
+
+use crate::errors::AppError;
+
+// Mark a recovery as used to enroll a passkey; each recovery token enrolls one.
+// Run it in the transaction that stores the new passkey, so a failed enrollment
+// leaves the recovery usable.
+pub async fn complete_recovery(
+    recovery_id: &str,
+    email: &str,
+    conn: &mut MySqlConnection,
+) -> Result<(), AppError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE account_recoveries
+        SET completed_at = UTC_TIMESTAMP()
+        WHERE id = ? AND user_email = ? AND completed_at IS NULL
+        "#,
+    )
+    .bind(recovery_id)
+    .bind(email)
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::conflict(
+            "recovery_used",
+            "This recovery has already been used to add a passkey.",
+        ));
+    }
+    Ok(())
+}
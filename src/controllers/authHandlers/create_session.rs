@@ -0,0 +1,28 @@
+use sqlx::MySqlPool;
+
+use crate::errors::AppError;
+
+// Record a new login session; `session_id` becomes the access token `jti`
+pub async fn create_session(
+    session_id: &str,
+    email: &str,
+    device: Option<&str>,
+    ip_address: Option<&str>,
+    pool: &MySqlPool,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO sessions (id, user_email, device, ip_address, last_seen_at)
+        VALUES (?, ?, ?, ?, UTC_TIMESTAMP())
+        "#,
+    )
+    .bind(session_id)
+    .bind(email)
+    // Truncate to the column width rather than failing the login
+    .bind(device.map(|device| device.chars().take(512).collect::<String>()))
+    .bind(ip_address)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use sqlx::{MySqlPool, Row};
+
+use crate::errors::AppError;
+
+// Remove one of `email`'s passkeys. The last passkey can't be removed, as the
+// account would become impossible to sign in to.
+pub async fn delete_credential(
+    email: &str,
+    credential_id: i64,
+    pool: &MySqlPool,
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    // Lock the user's credentials so two concurrent deletes can't remove the last two
+    let ids: Vec<i64> = sqlx::query(
+        r#"
+        SELECT uc.id
+        FROM user_credentials uc
+        INNER JOIN users u ON uc.user_id = u.id
+        WHERE u.email = ?
+        FOR UPDATE
+        "#,
+    )
+    .bind(email)
+    .fetch_all(&mut tx)
+    .await?
+    .iter()
+    .map(|row| row.get("id"))
+    .collect();
+
+    if !ids.contains(&credential_id) {
+        return Err(AppError::not_found("credential_not_found", "Passkey not found."));
+    }
+    if ids.len() == 1 {
+        return Err(AppError::conflict(
+            "last_credential",
+            "The last passkey on an account can't be removed.",
+        ));
+    }
+
+    sqlx::query("DELETE FROM user_credentials WHERE id = ?")
+        .bind(credential_id)
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
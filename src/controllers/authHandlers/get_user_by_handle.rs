@@ -0,0 +1,17 @@
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+use crate::errors::AppError;
+
+// Resolve the account a discoverable passkey belongs to from its user handle
+pub async fn get_user_by_handle(user_handle: Uuid, pool: &MySqlPool) -> Result<String, AppError> {
+    let row = sqlx::query("SELECT email FROM users WHERE user_handle = ?")
+        .bind(user_handle.to_string())
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::unauthorized("authentication_failed", "Failed to finish authentication.")
+        })?;
+
+    Ok(row.get("email"))
+}
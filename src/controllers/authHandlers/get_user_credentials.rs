@@ -19,9 +19,13 @@ pub async fn get_user_credentials(
 
     let rows = sqlx::query(query).bind(email).fetch_all(pool).await.ok()?;
 
+    // credential_id holds the JSON encoded id bytes, see `credential_id_key`
     let credentials = rows
         .into_iter()
-        .map(|row| row.get::<Vec<u8>, _>("credential_id").into())
+        .filter_map(|row| {
+            serde_json::from_slice::<Vec<u8>>(&row.get::<Vec<u8>, _>("credential_id")).ok()
+        })
+        .map(CredentialID::from)
         .collect::<Vec<CredentialID>>();
 
     Some(credentials)
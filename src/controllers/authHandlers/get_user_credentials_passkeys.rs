@@ -6,10 +6,12 @@ use webauthn_rs::prelude::{
     AuthenticationResult, CredentialID, Passkey, PasskeyAuthentication, PasskeyRegistration,
 };
 
+use crate::errors::AppError;
+
 pub async fn get_user_credentials_passkeys(
     email: &str,
     pool: &sqlx::MySqlPool,
-) -> Option<Vec<Passkey>> {
+) -> Result<Vec<Passkey>, AppError> {
     // Query to get the serialized passkeys from the database
     let query = r#"
         SELECT uc.passkey
@@ -21,20 +23,15 @@ pub async fn get_user_credentials_passkeys(
     let rows = sqlx::query(query)
         .bind(email) // Bind the email to the query
         .fetch_all(pool)
-        .await
-        .ok()?; // If fetching fails, return None
-
-    if rows.is_empty() {
-        return None; // No passkeys found for the user
-    }
+        .await?;
 
-    let passkeys = rows
-        .into_iter()
+    // An empty list means no passkeys are registered for the user
+    rows.into_iter()
         .map(|row| {
             let passkey: String = row.get("passkey");
-            serde_json::from_str(&passkey).expect("Failed to deserialize Passkey")
+            serde_json::from_str(&passkey).map_err(|err| {
+                AppError::internal(format!("Failed to deserialize Passkey: {}", err))
+            })
         })
-        .collect::<Vec<Passkey>>();
-
-    Some(passkeys)
+        .collect::<Result<Vec<Passkey>, AppError>>()
 }
@@ -0,0 +1,16 @@
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+use crate::errors::AppError;
+
+// The WebAuthn user handle of an existing account, shared by all of its passkeys
+pub async fn get_user_handle(email: &str, pool: &MySqlPool) -> Result<Uuid, AppError> {
+    let row = sqlx::query("SELECT user_handle FROM users WHERE email = ?")
+        .bind(email)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::not_found("user_not_found", "User not found."))?;
+
+    Uuid::parse_str(&row.get::<String, _>("user_handle"))
+        .map_err(|err| AppError::internal(format!("Invalid user handle for {}: {}", email, err)))
+}
@@ -0,0 +1,16 @@
+use sqlx::MySqlPool;
+
+use crate::errors::AppError;
+
+// Site wide roles of `email`, copied into access tokens for the frontend.
+// Authorization checks read the database, not these claims.
+pub async fn get_user_roles(email: &str, pool: &MySqlPool) -> Result<Vec<String>, AppError> {
+    let roles = sqlx::query_scalar::<_, String>(
+        "SELECT role FROM user_roles WHERE user_email = ? ORDER BY role",
+    )
+    .bind(email)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(roles)
+}
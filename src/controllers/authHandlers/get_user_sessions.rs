@@ -0,0 +1,41 @@
+use serde::Serialize;
+use sqlx::{MySqlPool, Row};
+
+use crate::errors::AppError;
+
+#[derive(Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub device: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: String,
+    pub last_seen_at: String,
+}
+
+// Sessions of `email` that have not been revoked, most recently used first
+pub async fn get_user_sessions(email: &str, pool: &MySqlPool) -> Result<Vec<SessionInfo>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, device, ip_address,
+               CAST(created_at AS CHAR) AS created_at,
+               CAST(last_seen_at AS CHAR) AS last_seen_at
+        FROM sessions
+        WHERE user_email = ? AND revoked_at IS NULL
+        ORDER BY last_seen_at DESC
+        "#,
+    )
+    .bind(email)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| SessionInfo {
+            id: row.get("id"),
+            device: row.get("device"),
+            ip_address: row.get("ip_address"),
+            created_at: row.get::<Option<String>, _>("created_at").unwrap_or_default(),
+            last_seen_at: row.get::<Option<String>, _>("last_seen_at").unwrap_or_default(),
+        })
+        .collect())
+}
@@ -0,0 +1,10 @@
+use sha2::{Digest, Sha256};
+
+// Hex SHA-256 of a random secret token. Only the hash of a bearer secret is
+// stored, so a database leak can't be replayed.
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,64 @@
+use sqlx::MySqlConnection;
+use uuid::Uuid;
+
+use super::hash_token::hash_token;
+use crate::errors::AppError;
+
+// Codes handed out per batch; issuing a new batch replaces the old one
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+// Codes are typed by hand, so ignore case, dashes and spaces before hashing
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hash_token(&normalized)
+}
+
+// 64 random bits from a v4 UUID (skipping its fixed version and variant bits),
+// shown as four dash separated groups: 1a2b-3c4d-5e6f-7a8b
+fn generate_recovery_code() -> String {
+    let uuid = Uuid::new_v4();
+    let bytes = uuid.as_bytes();
+    let hex: String = bytes[..6]
+        .iter()
+        .chain(&bytes[10..12])
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("{}-{}-{}-{}", &hex[..4], &hex[4..8], &hex[8..12], &hex[12..])
+}
+
+// Replace the recovery codes of `email` with a fresh batch and return the plaintext
+// codes. They are only ever shown this once. Run it in a transaction so a failure
+// can't leave the account with half a batch.
+pub async fn issue_recovery_codes(
+    email: &str,
+    conn: &mut MySqlConnection,
+) -> Result<Vec<String>, AppError> {
+    sqlx::query(
+        "DELETE FROM recovery_codes WHERE user_id = (SELECT id FROM users WHERE email = ?)",
+    )
+    .bind(email)
+    .execute(&mut *conn)
+    .await?;
+
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    for code in &codes {
+        sqlx::query(
+            r#"
+            INSERT INTO recovery_codes (user_id, code_hash, created_at)
+            VALUES ((SELECT id FROM users WHERE email = ?), ?, UTC_TIMESTAMP())
+            "#,
+        )
+        .bind(email)
+        .bind(hash_recovery_code(code))
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(codes)
+}
@@ -0,0 +1,31 @@
+use sqlx::{MySql, MySqlExecutor};
+use uuid::Uuid;
+
+use super::hash_token::hash_token;
+use crate::errors::AppError;
+
+// Create a new refresh token in `family_id` and return its plaintext value
+pub async fn issue_refresh_token<'e>(
+    email: &str,
+    family_id: &str,
+    ttl_secs: u64,
+    executor: impl MySqlExecutor<'e>,
+) -> Result<String, AppError> {
+    // Two v4 UUIDs give 244 random bits
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (token_hash, user_email, family_id, expires_at)
+        VALUES (?, ?, ?, DATE_ADD(UTC_TIMESTAMP(), INTERVAL ? SECOND))
+        "#,
+    )
+    .bind(hash_token(&token))
+    .bind(email)
+    .bind(family_id)
+    .bind(ttl_secs)
+    .execute(executor)
+    .await?;
+
+    Ok(token)
+}
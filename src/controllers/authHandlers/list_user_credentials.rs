@@ -0,0 +1,52 @@
+use serde::Serialize;
+use sqlx::{MySqlPool, Row};
+
+use crate::errors::AppError;
+
+#[derive(Serialize)]
+pub struct CredentialInfo {
+    pub id: i64,
+    pub nickname: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub backup_eligible: bool,
+    pub backup_state: bool,
+    // Set when the signature counter went backwards (possible clone)
+    pub counter_regressed_at: Option<String>,
+}
+
+// Passkeys registered to `email`, oldest first
+pub async fn list_user_credentials(
+    email: &str,
+    pool: &MySqlPool,
+) -> Result<Vec<CredentialInfo>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT uc.id, uc.nickname,
+               CAST(uc.created_at AS CHAR) AS created_at,
+               CAST(uc.last_used_at AS CHAR) AS last_used_at,
+               uc.backup_eligible, uc.backup_state,
+               CAST(uc.counter_regressed_at AS CHAR) AS counter_regressed_at
+        FROM user_credentials uc
+        INNER JOIN users u ON uc.user_id = u.id
+        WHERE u.email = ?
+        ORDER BY uc.id
+        "#,
+    )
+    .bind(email)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| CredentialInfo {
+            id: row.get("id"),
+            nickname: row.get("nickname"),
+            created_at: row.get::<Option<String>, _>("created_at").unwrap_or_default(),
+            last_used_at: row.get("last_used_at"),
+            backup_eligible: row.get("backup_eligible"),
+            backup_state: row.get("backup_state"),
+            counter_regressed_at: row.get("counter_regressed_at"),
+        })
+        .collect())
+}
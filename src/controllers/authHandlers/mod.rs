@@ -1,19 +1,56 @@
-pub mod get_passkey_auth_state;
-pub mod get_passkey_registration;
+pub mod ceremony_state;
+pub mod complete_recovery;
+pub mod create_session;
+pub mod delete_credential;
+pub mod get_user_by_handle;
 pub mod get_user_credentials;
 pub mod get_user_credentials_passkeys;
+pub mod get_user_handle;
+pub mod get_user_roles;
+pub mod get_user_sessions;
+pub mod hash_token;
+pub mod issue_recovery_codes;
+pub mod issue_refresh_token;
+pub mod list_user_credentials;
+pub mod redeem_recovery_code;
+pub mod rename_credential;
+pub mod revoke_session;
+pub mod rotate_refresh_token;
 pub mod store_passkey_auth_state;
 pub mod store_passkey_registration;
 pub mod store_user_credential;
+pub mod take_passkey_auth_state;
+pub mod take_passkey_registration;
+pub mod touch_session;
 pub mod update_credential_counter;
 
-pub use get_passkey_auth_state::get_passkey_auth_state;
-pub use get_passkey_registration::get_passkey_registration;
+pub use ceremony_state::{
+    store_ceremony_state, sweep_expired_ceremonies, take_ceremony_state, CeremonyChallenge,
+    CeremonyKind,
+};
+pub use complete_recovery::complete_recovery;
+pub use create_session::create_session;
+pub use delete_credential::delete_credential;
+pub use get_user_by_handle::get_user_by_handle;
 pub use get_user_credentials::get_user_credentials;
 pub use get_user_credentials_passkeys::get_user_credentials_passkeys;
+pub use get_user_handle::get_user_handle;
+pub use get_user_roles::get_user_roles;
+pub use get_user_sessions::{get_user_sessions, SessionInfo};
+pub use hash_token::hash_token;
+pub use issue_recovery_codes::{hash_recovery_code, issue_recovery_codes, RECOVERY_CODE_COUNT};
+pub use issue_refresh_token::issue_refresh_token;
+pub use list_user_credentials::{list_user_credentials, CredentialInfo};
+pub use redeem_recovery_code::redeem_recovery_code;
+pub use rename_credential::rename_credential;
+pub use revoke_session::revoke_session;
+pub use rotate_refresh_token::{rotate_refresh_token, RotatedRefreshToken};
 pub use store_passkey_auth_state::store_passkey_auth_state;
-pub use store_passkey_registration::store_passkey_registration;
-pub use store_user_credential::store_user_credential;
+pub use store_passkey_registration::{store_passkey_registration, store_registration_state};
+pub use store_user_credential::{credential_id_key, store_user_credential};
+pub use take_passkey_auth_state::take_passkey_auth_state;
+pub use take_passkey_registration::take_passkey_registration;
+pub use touch_session::touch_session;
 pub use update_credential_counter::update_credential_counter;
 
 // use std::{option, string};
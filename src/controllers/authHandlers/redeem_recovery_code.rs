@@ -0,0 +1,59 @@
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+use super::issue_recovery_codes::hash_recovery_code;
+use crate::errors::AppError;
+
+// Spend one unused recovery code of `email` and record the recovery in the audit
+// table. Returns the recovery id, which becomes the recovery token `jti`.
+pub async fn redeem_recovery_code(
+    email: &str,
+    code: &str,
+    device: Option<&str>,
+    ip_address: Option<&str>,
+    pool: &MySqlPool,
+) -> Result<String, AppError> {
+    let mut tx = pool.begin().await?;
+
+    // Unknown email and wrong code look the same to the caller
+    let code_id: i64 = sqlx::query(
+        r#"
+        SELECT rc.id
+        FROM recovery_codes rc
+        INNER JOIN users u ON u.id = rc.user_id
+        WHERE u.email = ? AND rc.code_hash = ? AND rc.used_at IS NULL
+        FOR UPDATE
+        "#,
+    )
+    .bind(email)
+    .bind(hash_recovery_code(code))
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| AppError::unauthorized("invalid_recovery_code", "Invalid recovery code."))?
+    .get("id");
+
+    sqlx::query("UPDATE recovery_codes SET used_at = UTC_TIMESTAMP() WHERE id = ?")
+        .bind(code_id)
+        .execute(&mut tx)
+        .await?;
+
+    let recovery_id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO account_recoveries
+            (id, user_email, recovery_code_id, device, ip_address, created_at)
+        VALUES (?, ?, ?, ?, ?, UTC_TIMESTAMP())
+        "#,
+    )
+    .bind(&recovery_id)
+    .bind(email)
+    .bind(code_id)
+    // Truncate to the column width rather than failing the recovery
+    .bind(device.map(|device| device.chars().take(512).collect::<String>()))
+    .bind(ip_address)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(recovery_id)
+}
@@ -0,0 +1,48 @@
+use sqlx::MySqlPool;
+
+use crate::errors::AppError;
+
+// Set the nickname of one of `email`'s passkeys
+pub async fn rename_credential(
+    email: &str,
+    credential_id: i64,
+    nickname: &str,
+    pool: &MySqlPool,
+) -> Result<(), AppError> {
+    let updated = sqlx::query(
+        r#"
+        UPDATE user_credentials uc
+        INNER JOIN users u ON uc.user_id = u.id
+        SET uc.nickname = ?
+        WHERE uc.id = ? AND u.email = ?
+        "#,
+    )
+    .bind(nickname)
+    .bind(credential_id)
+    .bind(email)
+    .execute(pool)
+    .await?;
+
+    // MySQL reports 0 affected rows when the nickname is unchanged, so confirm the row exists
+    if updated.rows_affected() == 0 {
+        let exists = sqlx::query(
+            r#"
+            SELECT 1
+            FROM user_credentials uc
+            INNER JOIN users u ON uc.user_id = u.id
+            WHERE uc.id = ? AND u.email = ?
+            "#,
+        )
+        .bind(credential_id)
+        .bind(email)
+        .fetch_optional(pool)
+        .await?
+        .is_some();
+
+        if !exists {
+            return Err(AppError::not_found("credential_not_found", "Passkey not found."));
+        }
+    }
+
+    Ok(())
+}
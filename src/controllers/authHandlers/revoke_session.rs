@@ -0,0 +1,44 @@
+use sqlx::MySqlPool;
+
+use crate::errors::AppError;
+
+// Revoke one session of `email`, or all of them when `session_id` is None.
+// The session's refresh tokens are revoked in the same transaction.
+// Returns the number of sessions revoked.
+pub async fn revoke_session(
+    email: &str,
+    session_id: Option<&str>,
+    pool: &MySqlPool,
+) -> Result<u64, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let revoked = sqlx::query(
+        r#"
+        UPDATE sessions
+        SET revoked_at = UTC_TIMESTAMP()
+        WHERE user_email = ? AND (? IS NULL OR id = ?) AND revoked_at IS NULL
+        "#,
+    )
+    .bind(email)
+    .bind(session_id)
+    .bind(session_id)
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = UTC_TIMESTAMP()
+        WHERE user_email = ? AND (? IS NULL OR family_id = ?) AND revoked_at IS NULL
+        "#,
+    )
+    .bind(email)
+    .bind(session_id)
+    .bind(session_id)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(revoked)
+}
@@ -0,0 +1,95 @@
+use sqlx::{MySqlPool, Row};
+
+use super::hash_token::hash_token;
+use super::issue_refresh_token::issue_refresh_token;
+use crate::errors::AppError;
+
+pub struct RotatedRefreshToken {
+    pub email: String,
+    pub family_id: String,
+    pub refresh_token: String,
+}
+
+// Exchange a refresh token for a new one in the same family. A token can be
+// used once; presenting it again means it leaked, so the family and its
+// login session are revoked.
+pub async fn rotate_refresh_token(
+    token: &str,
+    ttl_secs: u64,
+    pool: &MySqlPool,
+) -> Result<RotatedRefreshToken, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT id, user_email, family_id,
+               used_at IS NOT NULL AS used,
+               revoked_at IS NOT NULL AS revoked,
+               expires_at < UTC_TIMESTAMP() AS expired
+        FROM refresh_tokens
+        WHERE token_hash = ?
+        FOR UPDATE
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| AppError::unauthorized("invalid_refresh_token", "Invalid refresh token."))?;
+
+    let id: i64 = row.get("id");
+    let email: String = row.get("user_email");
+    let family_id: String = row.get("family_id");
+
+    if row.get::<bool, _>("revoked") {
+        return Err(AppError::unauthorized("invalid_refresh_token", "Refresh token has been revoked."));
+    }
+
+    if row.get::<bool, _>("used") {
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = UTC_TIMESTAMP()
+            WHERE family_id = ? AND revoked_at IS NULL
+            "#,
+        )
+        .bind(&family_id)
+        .execute(&mut tx)
+        .await?;
+        // The family id is the session id: its access tokens stop working too
+        sqlx::query(
+            r#"
+            UPDATE sessions
+            SET revoked_at = UTC_TIMESTAMP()
+            WHERE id = ? AND revoked_at IS NULL
+            "#,
+        )
+        .bind(&family_id)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        eprintln!("Refresh token reuse detected for {}, revoked session {}", email, family_id);
+        return Err(AppError::unauthorized(
+            "refresh_token_reused",
+            "Refresh token was already used; sign in again.",
+        ));
+    }
+
+    if row.get::<bool, _>("expired") {
+        return Err(AppError::unauthorized("refresh_token_expired", "Refresh token has expired."));
+    }
+
+    sqlx::query("UPDATE refresh_tokens SET used_at = UTC_TIMESTAMP() WHERE id = ?")
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    let refresh_token = issue_refresh_token(&email, &family_id, ttl_secs, &mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(RotatedRefreshToken {
+        email,
+        family_id,
+        refresh_token,
+    })
+}
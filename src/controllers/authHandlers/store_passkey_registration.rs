@@ -1,63 +1,51 @@
-use actix_web::web::Json;
-use serde_json::{from_str, to_string};
-use sqlx::{query, MySqlPool, Row};
-use webauthn_rs::prelude::*;
-use webauthn_rs::prelude::{
-    AuthenticationResult, CredentialID, Passkey, PasskeyAuthentication, PasskeyRegistration,
-};
+use sqlx::MySqlPool;
+use uuid::Uuid;
+use webauthn_rs::prelude::PasskeyRegistration;
 
+use super::ceremony_state::{store_ceremony_state, CeremonyKind};
+use crate::errors::AppError;
+
+// Create the user (or refresh an abandoned registration) and remember the
+// registration ceremony state. Returns the ceremony id.
 pub async fn store_passkey_registration(
     email: &str,
     display_name: &str,
+    user_handle: Uuid,
     registration: &PasskeyRegistration,
+    ttl_secs: u64,
     pool: &MySqlPool,
-) {
+) -> Result<String, AppError> {
     let query = r#"
-        INSERT INTO users (email, display_name)
-        VALUES (?, ?)
+        INSERT INTO users (email, display_name, user_handle)
+        VALUES (?, ?, ?)
+        ON DUPLICATE KEY UPDATE
+            display_name = VALUES(display_name)
     "#;
-    let result = sqlx::query(query)
+    sqlx::query(query)
         .bind(email)
         .bind(display_name)
+        .bind(user_handle.to_string())
         .execute(pool)
-        .await;
-    match result {
-        Ok(_) => {
-            println!("User stored successfully for email: {}", email);
-        }
-        Err(err) => {
-            eprintln!("Failed to store user: {}", err);
-        }
-    }
-
-    // Serialize the registration object to JSON
-    let registration_data = to_string(registration).unwrap();
+        .await?;
+    println!("User stored successfully for email: {}", email);
 
-    // SQL query to insert passkey registration state into the database
-    let query = r#"
-        INSERT INTO user_passkey_registrations (user_id, registration_data)
-        VALUES (
-            (SELECT id FROM users WHERE email = ?),
-            ?
-        )
-    "#;
+    store_registration_state(email, registration, ttl_secs, pool).await
+}
 
-    // Execute the insert query
-    let result = sqlx::query(query)
-        .bind(email) // Bind the email to get user ID
-        .bind(registration_data) // Bind the serialized registration data
-        .execute(pool) // Execute the query on the provided MySQL pool
-        .await;
+// Remember the registration ceremony state of an existing user and return its ceremony id
+pub async fn store_registration_state(
+    email: &str,
+    registration: &PasskeyRegistration,
+    ttl_secs: u64,
+    pool: &MySqlPool,
+) -> Result<String, AppError> {
+    let ceremony_id =
+        store_ceremony_state(CeremonyKind::Registration, Some(email), registration, ttl_secs, pool)
+            .await?;
 
-    match result {
-        Ok(_) => {
-            println!(
-                "Passkey registration stored successfully for email: {}",
-                email
-            );
-        }
-        Err(err) => {
-            eprintln!("Failed to store passkey registration: {}", err);
-        }
-    }
+    println!(
+        "Passkey registration stored successfully for email: {}",
+        email
+    );
+    Ok(ceremony_id)
 }
@@ -1,31 +1,52 @@
 use actix_web::web::Json;
 use serde_json::{from_str, to_string};
-use sqlx::{query, MySqlPool, Row};
+use sqlx::{query, MySqlConnection, MySqlPool, Row};
 use webauthn_rs::prelude::*;
 use webauthn_rs::prelude::{
     AuthenticationResult, CredentialID, Passkey, PasskeyAuthentication, PasskeyRegistration,
 };
 
-pub async fn store_user_credential(email: &str, passkey: &Passkey, pool: &sqlx::MySqlPool) {
+use crate::errors::{is_duplicate_entry, AppError};
+
+// Key used for the credential_id column: the JSON encoding of the raw id bytes
+pub fn credential_id_key(cred_id: &CredentialID) -> Result<String, AppError> {
+    serde_json::to_string(&**cred_id)
+        .map_err(|err| AppError::internal(format!("Failed to serialize credential id: {}", err)))
+}
+
+// Add a passkey to the user's account; an account can hold several.
+// Takes a connection so callers can store it inside their own transaction.
+pub async fn store_user_credential(
+    email: &str,
+    passkey: &Passkey,
+    nickname: Option<&str>,
+    conn: &mut MySqlConnection,
+) -> Result<(), AppError> {
     // Serialize the Passkey object to binary (you may need to use a specific serialization method)
-    let passkey_blob = serde_json::to_string(passkey).expect("Failed to serialize Passkey");
-    let cred_id = serde_json::to_string(&**passkey.cred_id()).expect("Failed to serialize Passkey");
+    let passkey_blob = serde_json::to_string(passkey)
+        .map_err(|err| AppError::internal(format!("Failed to serialize Passkey: {}", err)))?;
+    let cred_id = credential_id_key(passkey.cred_id())?;
 
     // Store the credential in the database
     sqlx::query(
         r#"
-        INSERT INTO user_credentials (user_id, credential_id, passkey)
-        VALUES ((SELECT id FROM users WHERE email = ? LIMIT 1), ?, ?)
-        ON DUPLICATE KEY UPDATE
-            credential_id = VALUES(credential_id),
-            passkey = VALUES(passkey)
+        INSERT INTO user_credentials (user_id, credential_id, passkey, nickname)
+        VALUES ((SELECT id FROM users WHERE email = ? LIMIT 1), ?, ?, ?)
     "#,
     )
     .bind(email)
-    .bind(cred_id) // Assuming credential ID is part of the Passkey
+    .bind(cred_id)
     .bind(passkey_blob)
-    .execute(pool)
+    .bind(nickname)
+    .execute(&mut *conn)
     .await
-    .expect("Failed to store or update user credential");
+    .map_err(|err| {
+        if is_duplicate_entry(&err) {
+            AppError::conflict("credential_exists", "This passkey is already registered.")
+        } else {
+            AppError::from(err)
+        }
+    })?;
     println!("User credential stored successfully for email: {}", email);
+    Ok(())
 }
@@ -0,0 +1,15 @@
+use sqlx::MySqlPool;
+use webauthn_rs::prelude::PasskeyAuthentication;
+
+use super::ceremony_state::{take_ceremony_state, CeremonyKind};
+use crate::errors::AppError;
+
+// Consume the authentication state stored under `ceremony_id` for `email`
+pub async fn take_passkey_auth_state(
+    ceremony_id: &str,
+    kind: CeremonyKind,
+    email: &str,
+    pool: &MySqlPool,
+) -> Result<PasskeyAuthentication, AppError> {
+    take_ceremony_state(ceremony_id, kind, Some(email), pool).await
+}
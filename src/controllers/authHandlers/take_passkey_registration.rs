@@ -0,0 +1,14 @@
+use sqlx::MySqlPool;
+use webauthn_rs::prelude::PasskeyRegistration;
+
+use super::ceremony_state::{take_ceremony_state, CeremonyKind};
+use crate::errors::AppError;
+
+// Consume the registration state stored under `ceremony_id` for `email`
+pub async fn take_passkey_registration(
+    ceremony_id: &str,
+    email: &str,
+    pool: &MySqlPool,
+) -> Result<PasskeyRegistration, AppError> {
+    take_ceremony_state(ceremony_id, CeremonyKind::Registration, Some(email), pool).await
+}
@@ -0,0 +1,36 @@
+use sqlx::MySqlPool;
+
+use crate::errors::AppError;
+
+// Check that a session is still live and record activity on it. Returns
+// false when the session is unknown, revoked or belongs to someone else.
+pub async fn touch_session(session_id: &str, email: &str, pool: &MySqlPool) -> Result<bool, AppError> {
+    let active = sqlx::query(
+        r#"
+        SELECT 1
+        FROM sessions
+        WHERE id = ? AND user_email = ? AND revoked_at IS NULL
+        "#,
+    )
+    .bind(session_id)
+    .bind(email)
+    .fetch_optional(pool)
+    .await?
+    .is_some();
+
+    if active {
+        // Only write once a minute so busy clients don't update the row on every request
+        sqlx::query(
+            r#"
+            UPDATE sessions
+            SET last_seen_at = UTC_TIMESTAMP()
+            WHERE id = ? AND (last_seen_at IS NULL OR last_seen_at < UTC_TIMESTAMP() - INTERVAL 60 SECOND)
+            "#,
+        )
+        .bind(session_id)
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(active)
+}
@@ -1,4 +1,85 @@
-pub async fn update_credential_counter(email: &str, new_counter: u32, pool: &sqlx::MySqlPool) {
-    // Update the stored credential counter in the database
-    println!("Updating credential counter for email: {}", email);
+use sqlx::Row;
+use webauthn_rs::prelude::{AuthenticationResult, Passkey};
+
+use super::store_user_credential::credential_id_key;
+use crate::config::CounterPolicy;
+use crate::errors::AppError;
+
+// Write the signature counter and backup flags of a successful authentication
+// back into the stored Passkey. A counter that fails to increase means the
+// authenticator may have been cloned; `policy` decides whether that login is
+// rejected or only flagged on the credential.
+pub async fn update_credential_counter(
+    auth_result: &AuthenticationResult,
+    policy: CounterPolicy,
+    pool: &sqlx::MySqlPool,
+) -> Result<(), AppError> {
+    let cred_id = credential_id_key(auth_result.cred_id())?;
+    let mut tx = pool.begin().await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT id, passkey, sign_count
+        FROM user_credentials
+        WHERE credential_id = ?
+        FOR UPDATE
+        "#,
+    )
+    .bind(&cred_id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| AppError::not_found("credential_not_found", "Passkey not found."))?;
+
+    let id: i64 = row.get("id");
+    let stored_count: u32 = row.get("sign_count");
+    let new_count = auth_result.counter();
+
+    // Authenticators that don't implement counters always report 0
+    let regressed = (new_count != 0 || stored_count != 0) && new_count <= stored_count;
+    if regressed {
+        eprintln!(
+            "Signature counter regressed for credential {} ({} <= {})",
+            id, new_count, stored_count
+        );
+        sqlx::query("UPDATE user_credentials SET counter_regressed_at = UTC_TIMESTAMP() WHERE id = ?")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+
+        if policy == CounterPolicy::Reject {
+            tx.commit().await?;
+            return Err(AppError::unauthorized(
+                "credential_counter_regressed",
+                "This passkey may have been cloned and can't be used to sign in.",
+            ));
+        }
+    }
+
+    let mut passkey: Passkey = serde_json::from_str(&row.get::<String, _>("passkey"))
+        .map_err(|err| AppError::internal(format!("Failed to deserialize Passkey: {}", err)))?;
+    passkey.update_credential(auth_result);
+    let passkey_blob = serde_json::to_string(&passkey)
+        .map_err(|err| AppError::internal(format!("Failed to serialize Passkey: {}", err)))?;
+
+    sqlx::query(
+        r#"
+        UPDATE user_credentials
+        SET passkey = ?,
+            sign_count = GREATEST(sign_count, ?),
+            backup_eligible = ?,
+            backup_state = ?,
+            last_used_at = UTC_TIMESTAMP()
+        WHERE id = ?
+        "#,
+    )
+    .bind(passkey_blob)
+    .bind(new_count)
+    .bind(auth_result.backup_eligible())
+    .bind(auth_result.backup_state())
+    .bind(id)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
 }
@@ -1,5 +1,6 @@
 use crate::messages::{
-    ClientActorMessage, Connect, Disconnect, GetOrCreateGroup, NotifyPollId, WsMessage,
+    ClientActorMessage, CloseConnection, Connect, Disconnect, GetOrCreateGroup, NotifyPollId,
+    NotifyPollStructure, PollDeleted, WsMessage,
 };
 use actix::prelude::{Actor, Context, Handler, Recipient};
 use std::collections::{HashMap, HashSet};
@@ -9,6 +10,7 @@ type Socket = Recipient<WsMessage>;
 
 pub struct Lobby {
     sessions: HashMap<Uuid, Socket>,     //self id to self
+    closers: HashMap<Uuid, Recipient<CloseConnection>>, //self id to its close handle
     rooms: HashMap<Uuid, HashSet<Uuid>>, //room id  to list of users id
     poll_to_group: HashMap<i64, Uuid>,   //poll id to group id
 }
@@ -17,6 +19,7 @@ impl Default for Lobby {
     fn default() -> Lobby {
         Lobby {
             sessions: HashMap::new(),
+            closers: HashMap::new(),
             rooms: HashMap::new(),
             poll_to_group: HashMap::new(),
         }
@@ -38,6 +41,18 @@ impl Lobby {
             println!("attempting to send message but couldn't find user id.");
         }
     }
+    // Send `message` to every client watching `poll_id`, if anyone is
+    fn broadcast_to_poll(&self, poll_id: i64, message: &str) {
+        if let Some(clients) = self
+            .poll_to_group
+            .get(&poll_id)
+            .and_then(|group_id| self.rooms.get(group_id))
+        {
+            clients
+                .iter()
+                .for_each(|client_id| self.send_message(message, client_id));
+        }
+    }
 }
 
 impl Actor for Lobby {
@@ -48,15 +63,16 @@ impl Handler<Disconnect> for Lobby {
     type Result = ();
 
     fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
+        self.closers.remove(&msg.id);
         if self.sessions.remove(&msg.id).is_some() {
-            self.rooms
-                .get(&msg.room_id)
-                .unwrap()
-                .iter()
-                .filter(|conn_id| *conn_id.to_owned() != msg.id)
-                .for_each(|user_id| {
-                    self.send_message(&format!("{} disconnected.", &msg.id), user_id)
-                });
+            // The room is already gone when its poll was deleted
+            if let Some(room) = self.rooms.get(&msg.room_id) {
+                room.iter()
+                    .filter(|conn_id| *conn_id.to_owned() != msg.id)
+                    .for_each(|user_id| {
+                        self.send_message(&format!("{} disconnected.", &msg.id), user_id)
+                    });
+            }
             if let Some(lobby) = self.rooms.get_mut(&msg.room_id) {
                 if lobby.len() > 1 {
                     lobby.remove(&msg.id);
@@ -88,6 +104,7 @@ impl Handler<Connect> for Lobby {
             });
 
         self.sessions.insert(msg.self_id, msg.addr);
+        self.closers.insert(msg.self_id, msg.closer);
 
         self.send_message(&format!("your id is {}", msg.self_id), &msg.self_id);
     }
@@ -155,3 +172,35 @@ impl Handler<NotifyPollId> for Lobby {
         }
     }
 }
+
+impl Handler<NotifyPollStructure> for Lobby {
+    type Result = ();
+
+    fn handle(&mut self, msg: NotifyPollStructure, _: &mut Context<Self>) -> Self::Result {
+        println!("Poll {} structure changed", msg.poll_id);
+        if let Some(group_id) = self.poll_to_group.get(&msg.poll_id) {
+            self.broadcast_to_poll(msg.poll_id, &format!("structure {}", group_id));
+        }
+    }
+}
+
+impl Handler<PollDeleted> for Lobby {
+    type Result = ();
+
+    fn handle(&mut self, msg: PollDeleted, _: &mut Context<Self>) -> Self::Result {
+        println!("Poll {} deleted", msg.poll_id);
+        // Forget the group so a later connection can't join the deleted poll's room
+        if let Some(group_id) = self.poll_to_group.remove(&msg.poll_id) {
+            if let Some(clients) = self.rooms.remove(&group_id) {
+                let reason = format!("deleted {}", group_id);
+                for client_id in clients {
+                    if let Some(closer) = self.closers.get(&client_id) {
+                        let _ = closer.do_send(CloseConnection {
+                            reason: reason.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
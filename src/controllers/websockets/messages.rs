@@ -9,6 +9,7 @@ pub struct WsMessage(pub String);
 #[rtype(result = "()")]
 pub struct Connect {
     pub addr: Recipient<WsMessage>,
+    pub closer: Recipient<CloseConnection>,
     pub lobby_id: Uuid,
     pub self_id: Uuid,
 }
@@ -39,3 +40,25 @@ pub struct GetOrCreateGroup {
 pub struct NotifyPollId {
     pub poll_id: i64,
 }
+
+// Questions or options of a poll were added, removed, edited or reordered;
+// clients should refetch the poll rather than just its scores
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct NotifyPollStructure {
+    pub poll_id: i64,
+}
+
+// The poll was deleted: send everyone watching it a final event and disconnect them
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PollDeleted {
+    pub poll_id: i64,
+}
+
+// Ask a connection to send `reason` as its last text frame and close
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseConnection {
+    pub reason: String,
+}
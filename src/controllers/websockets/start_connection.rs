@@ -8,6 +8,10 @@ use actix_web::{
     Error, HttpRequest, HttpResponse,
 };
 use actix_web_actors::ws;
+use sqlx::MySqlPool;
+
+use crate::config::MaybeAuthUser;
+use crate::polls::{require_poll_access, PollRole, ShareToken};
 
 #[get("ws/{poll_id}")]
 pub async fn start_connection(
@@ -15,16 +19,22 @@ pub async fn start_connection(
     stream: Payload,
     poll_id: Path<i64>, // poll_id as i64
     srv: Data<Addr<Lobby>>,
+    pool: Data<MySqlPool>,
+    user: MaybeAuthUser,
+    share: ShareToken,
 ) -> Result<HttpResponse, Error> {
     println!("start_connection");
+    // Live results are only streamed to callers who may see the poll
+    let poll_id = poll_id.into_inner();
+    require_poll_access(user.0.as_ref(), share.as_deref(), poll_id, PollRole::Viewer, &pool)
+        .await?;
+
     // Get or create a group for the poll_id
 
     let group_id = {
         let mut lobby = srv.get_ref().clone();
         match lobby
-            .send(GetOrCreateGroup {
-                poll_id: poll_id.into_inner(),
-            })
+            .send(GetOrCreateGroup { poll_id })
             .await
         {
             Ok(Ok(group_id)) => group_id,
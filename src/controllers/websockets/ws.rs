@@ -1,5 +1,5 @@
 use crate::lobby::Lobby;
-use crate::messages::{ClientActorMessage, Connect, Disconnect, WsMessage};
+use crate::messages::{ClientActorMessage, CloseConnection, Connect, Disconnect, WsMessage};
 use actix::{fut, ActorContext, ActorFuture, ActorFutureExt, ContextFutureSpawner, WrapFuture};
 use actix::{Actor, Addr, Running, StreamHandler};
 use actix::{AsyncContext, Handler};
@@ -39,7 +39,8 @@ impl Actor for WsConn {
         let addr = ctx.address();
         self.lobby_addr
             .send(Connect {
-                addr: addr.recipient(),
+                addr: addr.clone().recipient(),
+                closer: addr.recipient(),
                 lobby_id: self.room,
                 self_id: self.id,
             })
@@ -113,3 +114,16 @@ impl Handler<WsMessage> for WsConn {
         ctx.text(msg.0);
     }
 }
+
+impl Handler<CloseConnection> for WsConn {
+    type Result = ();
+
+    fn handle(&mut self, msg: CloseConnection, ctx: &mut Self::Context) {
+        ctx.text(msg.reason);
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Normal,
+            description: None,
+        }));
+        ctx.stop();
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+use sqlx::mysql::MySqlDatabaseError;
+
+// MySQL error number for a duplicate entry on a unique key
+const ER_DUP_ENTRY: u16 = 1062;
+
+// A single invalid field reported by a validation error
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+// Crate-wide error returned by handlers. Every variant carries a stable,
+// machine readable `code` so the frontend never has to match on `detail`.
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(&'static str, String),
+    Unauthorized(&'static str, String),
+    Forbidden(&'static str, String),
+    NotFound(&'static str, String),
+    Conflict(&'static str, String),
+    Validation(Vec<FieldError>),
+    Database(sqlx::Error),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn bad_request(code: &'static str, detail: impl Into<String>) -> Self {
+        AppError::BadRequest(code, detail.into())
+    }
+
+    pub fn unauthorized(code: &'static str, detail: impl Into<String>) -> Self {
+        AppError::Unauthorized(code, detail.into())
+    }
+
+    pub fn forbidden(code: &'static str, detail: impl Into<String>) -> Self {
+        AppError::Forbidden(code, detail.into())
+    }
+
+    pub fn not_found(code: &'static str, detail: impl Into<String>) -> Self {
+        AppError::NotFound(code, detail.into())
+    }
+
+    pub fn conflict(code: &'static str, detail: impl Into<String>) -> Self {
+        AppError::Conflict(code, detail.into())
+    }
+
+    pub fn internal(detail: impl Into<String>) -> Self {
+        AppError::Internal(detail.into())
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(code, _)
+            | AppError::Unauthorized(code, _)
+            | AppError::Forbidden(code, _)
+            | AppError::NotFound(code, _)
+            | AppError::Conflict(code, _) => code,
+            AppError::Validation(_) => "validation_failed",
+            AppError::Database(_) => "database_error",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    // Human readable explanation; internal failures are not leaked to clients
+    fn detail(&self) -> String {
+        match self {
+            AppError::BadRequest(_, detail)
+            | AppError::Unauthorized(_, detail)
+            | AppError::Forbidden(_, detail)
+            | AppError::NotFound(_, detail)
+            | AppError::Conflict(_, detail) => detail.clone(),
+            AppError::Validation(errors) => {
+                format!("{} field(s) failed validation.", errors.len())
+            }
+            AppError::Database(_) => "A database error occurred.".to_string(),
+            AppError::Internal(_) => "An internal error occurred.".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Database(err) => write!(f, "{}: {}", self.code(), err),
+            AppError::Internal(detail) => write!(f, "{}: {}", self.code(), detail),
+            _ => write!(f, "{}: {}", self.code(), self.detail()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+// RFC 7807 problem details body
+#[derive(Serialize)]
+struct Problem<'a> {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: &'a str,
+    status: u16,
+    detail: String,
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<&'a [FieldError]>,
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(..) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(..) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(..) => StatusCode::FORBIDDEN,
+            AppError::NotFound(..) => StatusCode::NOT_FOUND,
+            AppError::Conflict(..) => StatusCode::CONFLICT,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let AppError::Database(_) | AppError::Internal(_) = self {
+            eprintln!("{}", self);
+        }
+
+        let status = self.status_code();
+        let problem = Problem {
+            problem_type: format!("/problems/{}", self.code()),
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code: self.code(),
+            errors: match self {
+                AppError::Validation(errors) => Some(errors),
+                _ => None,
+            },
+        };
+
+        HttpResponse::build(status)
+            .content_type("application/problem+json")
+            .json(problem)
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => AppError::not_found("not_found", "Resource not found."),
+            err => AppError::Database(err),
+        }
+    }
+}
+
+// True when the error is MySQL rejecting a row that violates a unique key
+pub fn is_duplicate_entry(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .and_then(|db_err| db_err.try_downcast_ref::<MySqlDatabaseError>())
+        .map_or(false, |mysql_err| mysql_err.number() == ER_DUP_ENTRY)
+}
@@ -8,12 +8,17 @@ use actix_web::web::{self, Data};
 use actix_web::{get, App, HttpMessage, HttpResponse, HttpServer, Responder};
 use routes::start_verification::start_verification;
 
-use std::{env, sync::Arc};
+use std::{env, sync::Arc, time::Duration};
 
 mod config;
-use config::{database_connection, jwt_middleware};
+mod errors;
+use errors::AppError;
+use config::{database_connection, jwt_middleware, Settings};
 use config::webauth_utilities::create_webauthn_instance;
 
+mod polls;
+use polls::{seed_admins, PollScheduler};
+
 mod controllers;
 use controllers::*;
 
@@ -28,14 +33,48 @@ use routes::auth::finish_registration::finish_registration;
 use routes::auth::register_start::register_start;
 use routes::auth::start_authentication::start_authentication;
 use routes::auth::finish_verification::finish_verification;
+use routes::auth::refresh_token::refresh_token;
+use routes::auth::recovery::{
+    finish_recovery_enrollment, recover_account, regenerate_recovery_codes,
+    start_recovery_enrollment,
+};
+use routes::auth::discoverable_authentication::{
+    finish_discoverable_authentication, start_discoverable_authentication,
+};
+use routes::auth::credentials::{
+    finish_credential_enrollment, list_credentials, revoke_credential, start_credential_enrollment,
+    update_credential,
+};
+use routes::auth::sessions::{delete_all_sessions, delete_session, list_sessions, logout};
+use routes::auth::user_roles::{grant_user_role, list_user_roles, revoke_user_role};
+
+use routes::organizations::invitations::{
+    accept_invitation, create_invitation, get_invitations, revoke_invitation,
+};
+use routes::organizations::organizations::{
+    create_organization, get_organization_members, get_organizations,
+    remove_organization_member,
+};
 
 use routes::close_poll::close_poll;
 use routes::is_question_attempted;
 use routes::polling::create_poll::{create_poll};
+use routes::polling::delete_poll::{archive_poll, delete_poll, restore_poll};
+use routes::polling::edit_poll::{
+    add_option, add_question, remove_option, remove_question, reorder_options, reorder_questions,
+    update_option, update_poll, update_question,
+};
 use routes::polling::get_polls::get_polls;
 use routes::polling::get_quiz::get_poll;
-use routes::polling::question_scores::get_question_scores;
-use routes::polling::vote_handler::crate_vote;
+use routes::polling::poll_access::{
+    create_poll_invite, get_poll_invites, remove_poll_invite, rotate_share_link, set_poll_visibility,
+};
+use routes::polling::poll_roles::{get_poll_roles, remove_poll_role, set_poll_role};
+use routes::polling::poll_status::{get_poll_status_history, set_poll_status};
+use routes::polling::question_scores::{
+    get_question_answers, get_question_results, get_question_scores,
+};
+use routes::polling::vote_handler::{crate_vote, retract_vote};
 use routes::reset_poll::reset_poll;
 
 // ws
@@ -58,18 +97,86 @@ async fn main() -> std::io::Result<()> {
     // std::env::set_var("RUST_LOG", "debug");
     // env_logger::init();
 
+    // Load config.toml (or APP_CONFIG) plus environment overrides; refuse to start on bad config
+    let settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
     // Establish the database connection
-    let database = database_connection()
+    let database = database_connection(&settings.database)
         .await
         .expect("Failed to create dbpool");
     println!("Connected to database");
 
+    // `--migrate-only` applies the schema migrations and exits without serving
+    if env::args().any(|arg| arg == "--migrate-only") {
+        println!("Migrations complete, exiting (--migrate-only)");
+        return Ok(());
+    }
+
+    seed_admins(&settings.access.admin_emails, &database)
+        .await
+        .expect("Failed to grant configured admin roles");
+
+    // One relying party for the whole server
+    let webauthn = Data::new(
+        create_webauthn_instance(&settings.webauthn)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?,
+    );
+    println!(
+        "WebAuthn relying party {} for origins {:?}",
+        settings.webauthn.rp_id, settings.webauthn.origins
+    );
+
+    // Expired challenges and registrations that never finished are cleaned up in the background
+    let sweep_pool = database.clone();
+    let ceremony_ttl = settings.webauthn.timeout_secs;
+    let mut sweep_interval =
+        actix_web::rt::time::interval(Duration::from_secs(settings.webauthn.sweep_interval_secs));
+    actix_web::rt::spawn(async move {
+        loop {
+            sweep_interval.tick().await;
+            match sweep_expired_ceremonies(ceremony_ttl, &sweep_pool).await {
+                Ok((0, 0)) => {}
+                Ok((ceremonies, users)) => println!(
+                    "Swept {} expired ceremonies and {} abandoned registrations",
+                    ceremonies, users
+                ),
+                Err(err) => eprintln!("Ceremony sweep failed: {}", err),
+            }
+        }
+    });
+
+    let bind_address = settings.bind_address();
+    let settings = Data::new(settings);
+
     //create and spin up a lobby
     let chat_server = Lobby::default().start();
 
+    // Opens scheduled polls and closes polls at their closes_at
+    PollScheduler::new(
+        database.clone(),
+        chat_server.clone(),
+        Duration::from_secs(settings.scheduler.interval_secs),
+    )
+    .start();
+
     let server = HttpServer::new(move || {
         App::new()
             .app_data(Data::new(database.clone()))
+            .app_data(webauthn.clone())
+            .app_data(settings.clone())
+            // Malformed bodies and query strings get the same problem+json shape as handler errors
+            .app_data(web::JsonConfig::default().error_handler(|err, _| {
+                AppError::bad_request("invalid_body", err.to_string()).into()
+            }))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                AppError::bad_request("invalid_query", err.to_string()).into()
+            }))
             .wrap(
                 Cors::default() // Allows all origins
                     .allow_any_origin() // Allows all origins
@@ -81,27 +188,79 @@ async fn main() -> std::io::Result<()> {
             .service(finish_registration)
             .service(start_authentication)
             .service(finish_authentication)
+            .service(start_discoverable_authentication)
+            .service(finish_discoverable_authentication)
+            .service(refresh_token)
+            .service(recover_account)
+            .service(start_recovery_enrollment)
+            .service(finish_recovery_enrollment)
 
+            .service(get_poll) // Anonymous callers only see public polls
             .service(get_question_scores)
+            .service(get_question_results)
             .service(start_connection) //register our route. rename with "as" import or naming conflict
             .app_data(Data::new(chat_server.clone())) //register the lobby
             .service(
                 web::scope("")
                     .wrap(from_fn(jwt_middleware))
-                    .service(get_poll) // JWT protected
-                    .service(get_polls)
+                    .service(get_polls) // JWT protected
                     .service(create_poll)
+                    .service(update_poll)
+                    .service(reorder_questions)
+                    .service(add_question)
+                    .service(update_question)
+                    .service(remove_question)
+                    .service(reorder_options)
+                    .service(add_option)
+                    .service(update_option)
+                    .service(remove_option)
+                    .service(get_question_answers)
+                    .service(archive_poll)
+                    .service(restore_poll)
+                    .service(set_poll_status)
+                    .service(get_poll_status_history)
+                    .service(delete_poll)
                     .service(crate_vote)
+                    .service(retract_vote)
                     .service(close_poll)
                     .service(start_verification)
                     .service(finish_verification)
                     .service(is_question_attempted)
-                    .service(reset_poll),
+                    .service(reset_poll)
+                    .service(list_sessions)
+                    .service(delete_session)
+                    .service(delete_all_sessions)
+                    .service(logout)
+                    .service(start_credential_enrollment)
+                    .service(finish_credential_enrollment)
+                    .service(list_credentials)
+                    .service(update_credential)
+                    .service(revoke_credential)
+                    .service(regenerate_recovery_codes)
+                    .service(get_poll_roles)
+                    .service(set_poll_role)
+                    .service(remove_poll_role)
+                    .service(set_poll_visibility)
+                    .service(rotate_share_link)
+                    .service(get_poll_invites)
+                    .service(create_poll_invite)
+                    .service(remove_poll_invite)
+                    .service(list_user_roles)
+                    .service(grant_user_role)
+                    .service(revoke_user_role)
+                    .service(create_organization)
+                    .service(get_organizations)
+                    .service(get_organization_members)
+                    .service(remove_organization_member)
+                    .service(create_invitation)
+                    .service(get_invitations)
+                    .service(revoke_invitation)
+                    .service(accept_invitation),
             )
     })
-    .bind(("0.0.0.0", 3001))?
+    .bind(bind_address.clone())?
     .run();
-    println!("Server running at http://localhost:3001");
+    println!("Server running at http://{}:{}", bind_address.0, bind_address.1);
     server.await
 }
 
@@ -0,0 +1,297 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{MySqlConnection, Row};
+
+use crate::errors::AppError;
+
+// Longest reason that can be attached to a status change, in characters
+pub const MAX_STATUS_REASON_LEN: usize = 500;
+
+// Where a poll is in its life. Only open polls (and scheduled polls whose
+// opens_at has passed, until the scheduler catches up) take votes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PollStatus {
+    Draft,     // being prepared, only visible to its editors
+    Scheduled, // waiting for its opens_at
+    Open,
+    Paused, // temporarily not taking votes
+    Closed,
+    Archived, // closed and hidden from listings
+}
+
+impl PollStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PollStatus::Draft => "draft",
+            PollStatus::Scheduled => "scheduled",
+            PollStatus::Open => "open",
+            PollStatus::Paused => "paused",
+            PollStatus::Closed => "closed",
+            PollStatus::Archived => "archived",
+        }
+    }
+
+    // Frozen until reopened: no votes and no edits
+    pub fn is_closed(self) -> bool {
+        matches!(self, PollStatus::Closed | PollStatus::Archived)
+    }
+
+    // The allowed moves. Anything but an archived poll can be archived;
+    // restoring an archived poll leaves it closed.
+    pub fn can_become(self, to: PollStatus) -> bool {
+        use PollStatus::*;
+        match (self, to) {
+            (Draft, Scheduled) | (Draft, Open) => true,
+            (Scheduled, Draft) | (Scheduled, Open) | (Scheduled, Closed) => true,
+            (Open, Paused) | (Open, Closed) => true,
+            (Paused, Open) | (Paused, Closed) => true,
+            (Closed, Open) => true,
+            (Archived, Closed) => true,
+            (from, Archived) => from != Archived,
+            _ => false,
+        }
+    }
+}
+
+// Where the scheduler moves a poll at `now`, if anywhere. A poll past its
+// closes_at is closed, even a scheduled one whose whole window passed before
+// it could be opened; otherwise a scheduled poll opens at its opens_at.
+pub fn scheduled_transition(
+    status: PollStatus,
+    opens_at: Option<NaiveDateTime>,
+    closes_at: Option<NaiveDateTime>,
+    now: NaiveDateTime,
+) -> Option<PollStatus> {
+    let running = matches!(status, PollStatus::Scheduled | PollStatus::Open | PollStatus::Paused);
+    if running && matches!(closes_at, Some(closes_at) if closes_at <= now) {
+        return Some(PollStatus::Closed);
+    }
+    if status == PollStatus::Scheduled && matches!(opens_at, Some(opens_at) if opens_at <= now) {
+        return Some(PollStatus::Open);
+    }
+    None
+}
+
+impl fmt::Display for PollStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PollStatus {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "draft" => Ok(PollStatus::Draft),
+            "scheduled" => Ok(PollStatus::Scheduled),
+            "open" => Ok(PollStatus::Open),
+            "paused" => Ok(PollStatus::Paused),
+            "closed" => Ok(PollStatus::Closed),
+            "archived" => Ok(PollStatus::Archived),
+            _ => Err(AppError::bad_request(
+                "invalid_status",
+                "Status must be one of draft, scheduled, open, paused, closed or archived.",
+            )),
+        }
+    }
+}
+
+// The poll's current status, locked until the transaction ends
+pub async fn lock_poll_status(
+    conn: &mut MySqlConnection,
+    poll_id: i64,
+) -> Result<PollStatus, AppError> {
+    sqlx::query("SELECT status FROM polls WHERE id = ? FOR UPDATE")
+        .bind(poll_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::not_found("poll_not_found", "Poll not found."))?
+        .get::<String, _>("status")
+        .parse()
+}
+
+// Move a poll to `to` and record who did it; `changed_by` is None for the
+// scheduler. Opening a poll early clears a future opens_at. When an editor
+// reopens a poll, a closes_at that already passed is cleared too, so the
+// scheduler doesn't close it again right away; the scheduler itself never
+// touches closes_at. Returns the status the poll had before.
+pub async fn transition_poll(
+    conn: &mut MySqlConnection,
+    poll_id: i64,
+    to: PollStatus,
+    changed_by: Option<&str>,
+    reason: Option<&str>,
+) -> Result<PollStatus, AppError> {
+    let from = lock_poll_status(&mut *conn, poll_id).await?;
+    if from == to {
+        return Err(AppError::conflict(
+            "invalid_transition",
+            format!("Poll is already {}.", to),
+        ));
+    }
+    if !from.can_become(to) {
+        return Err(AppError::conflict(
+            "invalid_transition",
+            format!("A {} poll can't become {}.", from, to),
+        ));
+    }
+
+    if to == PollStatus::Scheduled {
+        let opens_later: bool = sqlx::query(
+            r#"
+            SELECT (opens_at IS NOT NULL AND opens_at > UTC_TIMESTAMP()) AS opens_later
+            FROM polls
+            WHERE id = ?
+            "#,
+        )
+        .bind(poll_id)
+        .fetch_one(&mut *conn)
+        .await?
+        .get("opens_later");
+        if !opens_later {
+            return Err(AppError::conflict(
+                "schedule_required",
+                "Set an opens_at in the future before scheduling the poll.",
+            ));
+        }
+    }
+    if to == PollStatus::Open {
+        sqlx::query(
+            r#"
+            UPDATE polls
+            SET opens_at = IF(opens_at > UTC_TIMESTAMP(), NULL, opens_at),
+                closes_at = IF(? AND closes_at <= UTC_TIMESTAMP(), NULL, closes_at)
+            WHERE id = ?
+            "#,
+        )
+        .bind(changed_by.is_some())
+        .bind(poll_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    sqlx::query("UPDATE polls SET status = ? WHERE id = ?")
+        .bind(to.as_str())
+        .bind(poll_id)
+        .execute(&mut *conn)
+        .await?;
+
+    record_status_change(&mut *conn, poll_id, Some(from), to, changed_by, reason).await?;
+    Ok(from)
+}
+
+pub async fn record_status_change(
+    conn: &mut MySqlConnection,
+    poll_id: i64,
+    from: Option<PollStatus>,
+    to: PollStatus,
+    changed_by: Option<&str>,
+    reason: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO poll_status_history
+            (poll_id, from_status, to_status, changed_by, reason, created_at)
+        VALUES (?, ?, ?, ?, ?, UTC_TIMESTAMP())
+        "#,
+    )
+    .bind(poll_id)
+    .bind(from.map(PollStatus::as_str))
+    .bind(to.as_str())
+    .bind(changed_by)
+    .bind(reason)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use PollStatus::*;
+
+    const ALL: [PollStatus; 6] = [Draft, Scheduled, Open, Paused, Closed, Archived];
+
+    fn at(hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 1, 31).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn can_become_allows_exactly_the_listed_moves() {
+        let allowed = [
+            (Draft, Scheduled),
+            (Draft, Open),
+            (Draft, Archived),
+            (Scheduled, Draft),
+            (Scheduled, Open),
+            (Scheduled, Closed),
+            (Scheduled, Archived),
+            (Open, Paused),
+            (Open, Closed),
+            (Open, Archived),
+            (Paused, Open),
+            (Paused, Closed),
+            (Paused, Archived),
+            (Closed, Open),
+            (Closed, Archived),
+            (Archived, Closed),
+        ];
+        for from in ALL {
+            for to in ALL {
+                assert_eq!(
+                    from.can_become(to),
+                    allowed.contains(&(from, to)),
+                    "{} -> {}",
+                    from,
+                    to
+                );
+            }
+        }
+        assert!(!Draft.can_become(Closed));
+        assert!(!Archived.can_become(Open));
+    }
+
+    #[test]
+    fn status_round_trips_through_its_name() {
+        for status in ALL {
+            assert_eq!(status.as_str().parse::<PollStatus>().unwrap(), status);
+        }
+        assert!("deleted".parse::<PollStatus>().is_err());
+    }
+
+    #[test]
+    fn scheduled_poll_opens_at_its_opening_time() {
+        assert_eq!(scheduled_transition(Scheduled, Some(at(9)), Some(at(17)), at(12)), Some(Open));
+        assert_eq!(scheduled_transition(Scheduled, Some(at(9)), None, at(9)), Some(Open));
+        assert_eq!(scheduled_transition(Scheduled, Some(at(15)), Some(at(17)), at(12)), None);
+    }
+
+    #[test]
+    fn scheduled_poll_whose_window_elapsed_is_closed() {
+        assert_eq!(
+            scheduled_transition(Scheduled, Some(at(9)), Some(at(11)), at(12)),
+            Some(Closed)
+        );
+    }
+
+    #[test]
+    fn running_polls_close_at_their_closing_time() {
+        assert_eq!(scheduled_transition(Open, Some(at(9)), Some(at(12)), at(12)), Some(Closed));
+        assert_eq!(scheduled_transition(Paused, None, Some(at(11)), at(12)), Some(Closed));
+        assert_eq!(scheduled_transition(Open, Some(at(9)), Some(at(17)), at(12)), None);
+        assert_eq!(scheduled_transition(Open, None, None, at(12)), None);
+    }
+
+    #[test]
+    fn scheduler_leaves_other_statuses_alone() {
+        for status in [Draft, Closed, Archived] {
+            assert_eq!(scheduled_transition(status, Some(at(9)), Some(at(11)), at(12)), None);
+        }
+    }
+}
@@ -0,0 +1,11 @@
+pub mod lifecycle;
+pub mod questions;
+pub mod roles;
+pub mod scheduler;
+pub mod tally;
+
+pub use lifecycle::*;
+pub use questions::*;
+pub use roles::*;
+pub use scheduler::*;
+pub use tally::*;
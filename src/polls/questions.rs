@@ -0,0 +1,508 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::MySqlRow;
+use sqlx::{MySqlConnection, Row};
+
+use crate::errors::{AppError, FieldError};
+
+// Longest free text answer, in characters
+pub const MAX_ANSWER_LEN: usize = 1000;
+// Rating scale used when a rating question doesn't set one
+const DEFAULT_SCALE: (i32, i32) = (1, 5);
+const MAX_SCALE_SPAN: i32 = 100;
+
+// How a question is answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuestionType {
+    Single,   // exactly one option
+    Multi,    // between min_selections and max_selections options
+    Ranked,   // options in order of preference
+    Rating,   // a number on the scale_min..=scale_max scale
+    FreeText, // a short text answer
+}
+
+impl Default for QuestionType {
+    fn default() -> Self {
+        QuestionType::Single
+    }
+}
+
+impl QuestionType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QuestionType::Single => "single",
+            QuestionType::Multi => "multi",
+            QuestionType::Ranked => "ranked",
+            QuestionType::Rating => "rating",
+            QuestionType::FreeText => "free_text",
+        }
+    }
+
+    // Whether ballots pick from the question's options
+    pub fn has_options(self) -> bool {
+        matches!(self, QuestionType::Single | QuestionType::Multi | QuestionType::Ranked)
+    }
+}
+
+impl FromStr for QuestionType {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "single" => Ok(QuestionType::Single),
+            "multi" => Ok(QuestionType::Multi),
+            "ranked" => Ok(QuestionType::Ranked),
+            "rating" => Ok(QuestionType::Rating),
+            "free_text" => Ok(QuestionType::FreeText),
+            _ => Err(AppError::bad_request(
+                "invalid_question_type",
+                "Question type must be one of single, multi, ranked, rating or free_text.",
+            )),
+        }
+    }
+}
+
+// A question's type and its limits, as sent when creating a question and
+// returned by `GET /api/polls/{id}`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuestionSpec {
+    #[serde(default)]
+    pub question_type: QuestionType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_selections: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_selections: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale_min: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale_max: Option<i32>,
+}
+
+impl QuestionSpec {
+    // Read the spec from a row selecting the questions columns of the same names
+    pub fn from_row(row: &MySqlRow) -> Result<Self, AppError> {
+        Ok(QuestionSpec {
+            question_type: row.get::<String, _>("question_type").parse()?,
+            min_selections: row.get("min_selections"),
+            max_selections: row.get("max_selections"),
+            scale_min: row.get("scale_min"),
+            scale_max: row.get("scale_max"),
+        })
+    }
+
+    // Fill in the default rating scale so it is stored explicitly
+    pub fn normalized(mut self) -> Self {
+        if self.question_type == QuestionType::Rating {
+            self.scale_min.get_or_insert(DEFAULT_SCALE.0);
+            self.scale_max.get_or_insert(DEFAULT_SCALE.1);
+        }
+        self
+    }
+
+    // Problems with the spec of a question with `option_count` options,
+    // reported under `prefix` (e.g. "questions[0]")
+    pub fn validate(&self, prefix: &str, option_count: usize) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let field = |name: &str| {
+            if prefix.is_empty() {
+                name.to_owned()
+            } else {
+                format!("{}.{}", prefix, name)
+            }
+        };
+
+        let selections = matches!(self.question_type, QuestionType::Multi | QuestionType::Ranked);
+        if !selections && (self.min_selections.is_some() || self.max_selections.is_some()) {
+            errors.push(FieldError::new(
+                field("min_selections"),
+                "Selection limits only apply to multi and ranked questions.",
+            ));
+        }
+        if selections {
+            let min = self.min_selections.unwrap_or(1);
+            let max = self.max_selections.unwrap_or(option_count as i32);
+            if min < 1 || min > max || max as usize > option_count {
+                errors.push(FieldError::new(
+                    field("max_selections"),
+                    format!(
+                        "Selections must satisfy 1 <= min_selections <= max_selections <= {}.",
+                        option_count
+                    ),
+                ));
+            }
+        }
+
+        if self.question_type != QuestionType::Rating
+            && (self.scale_min.is_some() || self.scale_max.is_some())
+        {
+            errors.push(FieldError::new(
+                field("scale_min"),
+                "A scale only applies to rating questions.",
+            ));
+        }
+        if self.question_type == QuestionType::Rating {
+            let min = self.scale_min.unwrap_or(DEFAULT_SCALE.0);
+            let max = self.scale_max.unwrap_or(DEFAULT_SCALE.1);
+            if min >= max || max - min > MAX_SCALE_SPAN {
+                errors.push(FieldError::new(
+                    field("scale_max"),
+                    format!(
+                        "scale_min must be below scale_max, at most {} apart.",
+                        MAX_SCALE_SPAN
+                    ),
+                ));
+            }
+        }
+
+        if !self.question_type.has_options() && option_count > 0 {
+            errors.push(FieldError::new(
+                field("options"),
+                "Rating and free text questions have no options.",
+            ));
+        }
+
+        errors
+    }
+
+    // Check `ballot` against this question and its current `option_ids`,
+    // returning it with the free text trimmed
+    pub fn check_ballot(&self, ballot: &Ballot, option_ids: &[i64]) -> Result<Ballot, AppError> {
+        let invalid = |detail: String| Err(AppError::bad_request("invalid_ballot", detail));
+
+        if self.question_type.has_options() {
+            if ballot.rating.is_some() || ballot.text.is_some() {
+                return invalid("This question takes option_ids only.".to_owned());
+            }
+            let mut seen = HashSet::new();
+            for option_id in &ballot.option_ids {
+                if !option_ids.contains(option_id) {
+                    return Err(AppError::bad_request(
+                        "invalid_option",
+                        "Invalid option for this question.",
+                    ));
+                }
+                if !seen.insert(*option_id) {
+                    return invalid("An option can be picked only once.".to_owned());
+                }
+            }
+        } else if !ballot.option_ids.is_empty() {
+            return invalid("This question has no options.".to_owned());
+        }
+
+        let picked = ballot.option_ids.len() as i32;
+        match self.question_type {
+            QuestionType::Single => {
+                if picked != 1 {
+                    return invalid("Pick exactly one option.".to_owned());
+                }
+            }
+            QuestionType::Multi | QuestionType::Ranked => {
+                let min = self.min_selections.unwrap_or(1);
+                let max = self.max_selections.unwrap_or(option_ids.len() as i32);
+                if picked < min || picked > max {
+                    return invalid(format!("Pick between {} and {} options.", min, max));
+                }
+            }
+            QuestionType::Rating => {
+                let min = self.scale_min.unwrap_or(DEFAULT_SCALE.0);
+                let max = self.scale_max.unwrap_or(DEFAULT_SCALE.1);
+                match ballot.rating {
+                    Some(rating) if ballot.text.is_none() && (min..=max).contains(&rating) => {}
+                    _ => return invalid(format!("Give a rating between {} and {}.", min, max)),
+                }
+            }
+            QuestionType::FreeText => {
+                let text = ballot.text.as_deref().map(str::trim).unwrap_or_default();
+                if ballot.rating.is_some() || text.is_empty() {
+                    return invalid("Give a text answer.".to_owned());
+                }
+                if text.chars().count() > MAX_ANSWER_LEN {
+                    return invalid(format!(
+                        "Answers must be at most {} characters.",
+                        MAX_ANSWER_LEN
+                    ));
+                }
+                return Ok(Ballot {
+                    text: Some(text.to_owned()),
+                    ..Ballot::default()
+                });
+            }
+        }
+
+        Ok(ballot.clone())
+    }
+
+    // Options whose running score a ballot adds to. A ranked ballot only
+    // counts for its first preference; the full ranking is in vote_selections.
+    pub fn scored_options<'a>(&self, ballot: &'a Ballot) -> &'a [i64] {
+        match self.question_type {
+            QuestionType::Ranked => &ballot.option_ids[..ballot.option_ids.len().min(1)],
+            _ => &ballot.option_ids,
+        }
+    }
+}
+
+// One user's answer to one question. `option_ids` holds the picked options,
+// in order of preference for ranked questions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ballot {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub option_ids: Vec<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+// The spec and option ids of `question_id`, failing with 400 when it isn't a
+// question of `poll_id`
+pub async fn load_question(
+    conn: &mut MySqlConnection,
+    poll_id: i64,
+    question_id: i64,
+) -> Result<(QuestionSpec, Vec<i64>), AppError> {
+    let question = sqlx::query(
+        r#"
+        SELECT question_type, min_selections, max_selections, scale_min, scale_max
+        FROM questions
+        WHERE id = ? AND poll_id = ?
+        "#,
+    )
+    .bind(question_id)
+    .bind(poll_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::bad_request("invalid_question", "Invalid question for this poll."))?;
+
+    let option_ids = sqlx::query("SELECT id FROM poll_options WHERE question_id = ?")
+        .bind(question_id)
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| row.get("id"))
+        .collect();
+
+    Ok((QuestionSpec::from_row(&question)?, option_ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: [i64; 4] = [10, 11, 12, 13];
+
+    fn spec(question_type: QuestionType) -> QuestionSpec {
+        QuestionSpec {
+            question_type,
+            ..QuestionSpec::default()
+        }
+    }
+
+    fn picks(option_ids: &[i64]) -> Ballot {
+        Ballot {
+            option_ids: option_ids.to_vec(),
+            ..Ballot::default()
+        }
+    }
+
+    fn rating(rating: i32) -> Ballot {
+        Ballot {
+            rating: Some(rating),
+            ..Ballot::default()
+        }
+    }
+
+    fn text(text: &str) -> Ballot {
+        Ballot {
+            text: Some(text.to_owned()),
+            ..Ballot::default()
+        }
+    }
+
+    fn fields(errors: &[FieldError]) -> Vec<&str> {
+        errors.iter().map(|error| error.field.as_str()).collect()
+    }
+
+    fn error_code(result: Result<Ballot, AppError>) -> &'static str {
+        result.expect_err("ballot should be refused").code()
+    }
+
+    #[test]
+    fn selection_limits_default_to_one_and_every_option() {
+        assert!(spec(QuestionType::Multi).validate("", 4).is_empty());
+        assert!(spec(QuestionType::Ranked).validate("", 2).is_empty());
+    }
+
+    #[test]
+    fn selection_limits_are_bounded_by_the_options() {
+        let within = QuestionSpec {
+            min_selections: Some(2),
+            max_selections: Some(4),
+            ..spec(QuestionType::Multi)
+        };
+        assert!(within.validate("", 4).is_empty());
+
+        for (min, max) in [(Some(0), None), (Some(3), Some(2)), (None, Some(5)), (Some(5), None)] {
+            let limits = QuestionSpec {
+                min_selections: min,
+                max_selections: max,
+                ..spec(QuestionType::Multi)
+            };
+            assert_eq!(
+                fields(&limits.validate("questions[1]", 4)),
+                ["questions[1].max_selections"],
+                "min {:?} max {:?}",
+                min,
+                max
+            );
+        }
+    }
+
+    #[test]
+    fn limits_only_apply_to_their_question_types() {
+        let single = QuestionSpec {
+            max_selections: Some(2),
+            ..spec(QuestionType::Single)
+        };
+        assert_eq!(fields(&single.validate("", 3)), ["min_selections"]);
+
+        let multi = QuestionSpec {
+            scale_max: Some(10),
+            ..spec(QuestionType::Multi)
+        };
+        assert_eq!(fields(&multi.validate("", 3)), ["scale_min"]);
+
+        assert_eq!(fields(&spec(QuestionType::Rating).validate("", 2)), ["options"]);
+        assert_eq!(fields(&spec(QuestionType::FreeText).validate("", 1)), ["options"]);
+    }
+
+    #[test]
+    fn rating_scale_must_be_ordered_and_bounded() {
+        let scale = |min, max| QuestionSpec {
+            scale_min: Some(min),
+            scale_max: Some(max),
+            ..spec(QuestionType::Rating)
+        };
+        assert!(scale(0, 10).validate("", 0).is_empty());
+        assert!(scale(-50, 50).validate("", 0).is_empty());
+        assert_eq!(fields(&scale(5, 5).validate("", 0)), ["scale_max"]);
+        assert_eq!(fields(&scale(5, 1).validate("", 0)), ["scale_max"]);
+        assert_eq!(fields(&scale(0, 101).validate("", 0)), ["scale_max"]);
+    }
+
+    #[test]
+    fn normalized_stores_the_default_scale_for_ratings_only() {
+        let rating = spec(QuestionType::Rating).normalized();
+        assert_eq!((rating.scale_min, rating.scale_max), (Some(1), Some(5)));
+
+        let custom = QuestionSpec {
+            scale_max: Some(10),
+            ..spec(QuestionType::Rating)
+        }
+        .normalized();
+        assert_eq!((custom.scale_min, custom.scale_max), (Some(1), Some(10)));
+
+        let multi = spec(QuestionType::Multi).normalized();
+        assert_eq!((multi.scale_min, multi.scale_max), (None, None));
+    }
+
+    #[test]
+    fn single_choice_takes_exactly_one_option() {
+        let single = spec(QuestionType::Single);
+        assert_eq!(single.check_ballot(&picks(&[11]), &OPTIONS).unwrap(), picks(&[11]));
+        assert_eq!(error_code(single.check_ballot(&picks(&[]), &OPTIONS)), "invalid_ballot");
+        assert_eq!(error_code(single.check_ballot(&picks(&[10, 11]), &OPTIONS)), "invalid_ballot");
+    }
+
+    #[test]
+    fn selections_must_fall_within_the_limits() {
+        let multi = QuestionSpec {
+            min_selections: Some(2),
+            max_selections: Some(3),
+            ..spec(QuestionType::Multi)
+        };
+        assert!(multi.check_ballot(&picks(&[10, 11]), &OPTIONS).is_ok());
+        assert!(multi.check_ballot(&picks(&[10, 11, 12]), &OPTIONS).is_ok());
+        assert_eq!(error_code(multi.check_ballot(&picks(&[10]), &OPTIONS)), "invalid_ballot");
+        assert_eq!(
+            error_code(multi.check_ballot(&picks(&[10, 11, 12, 13]), &OPTIONS)),
+            "invalid_ballot"
+        );
+
+        // Without limits a ranking may cover one option up to all of them
+        let ranked = spec(QuestionType::Ranked);
+        assert!(ranked.check_ballot(&picks(&[13]), &OPTIONS).is_ok());
+        assert!(ranked.check_ballot(&picks(&[13, 12, 11, 10]), &OPTIONS).is_ok());
+        assert_eq!(error_code(ranked.check_ballot(&picks(&[]), &OPTIONS)), "invalid_ballot");
+    }
+
+    #[test]
+    fn ballots_refuse_duplicate_and_unknown_options() {
+        let ranked = spec(QuestionType::Ranked);
+        assert_eq!(error_code(ranked.check_ballot(&picks(&[10, 10]), &OPTIONS)), "invalid_ballot");
+        assert_eq!(error_code(ranked.check_ballot(&picks(&[10, 99]), &OPTIONS)), "invalid_option");
+        assert_eq!(
+            error_code(spec(QuestionType::Single).check_ballot(&picks(&[99]), &OPTIONS)),
+            "invalid_option"
+        );
+    }
+
+    #[test]
+    fn option_questions_refuse_ratings_and_text() {
+        let mut ballot = picks(&[10]);
+        ballot.rating = Some(3);
+        assert_eq!(
+            error_code(spec(QuestionType::Single).check_ballot(&ballot, &OPTIONS)),
+            "invalid_ballot"
+        );
+        assert_eq!(
+            error_code(spec(QuestionType::Rating).check_ballot(&picks(&[10]), &[])),
+            "invalid_ballot"
+        );
+    }
+
+    #[test]
+    fn ratings_must_be_on_the_scale() {
+        let rating = QuestionSpec {
+            scale_min: Some(0),
+            scale_max: Some(10),
+            ..spec(QuestionType::Rating)
+        };
+        assert!(rating.check_ballot(&self::rating(0), &[]).is_ok());
+        assert!(rating.check_ballot(&self::rating(10), &[]).is_ok());
+        assert_eq!(error_code(rating.check_ballot(&self::rating(-1), &[])), "invalid_ballot");
+        assert_eq!(error_code(rating.check_ballot(&self::rating(11), &[])), "invalid_ballot");
+        assert_eq!(error_code(rating.check_ballot(&Ballot::default(), &[])), "invalid_ballot");
+
+        // The default scale applies when none was stored
+        let default_scale = spec(QuestionType::Rating);
+        assert!(default_scale.check_ballot(&self::rating(5), &[]).is_ok());
+        assert_eq!(
+            error_code(default_scale.check_ballot(&self::rating(6), &[])),
+            "invalid_ballot"
+        );
+    }
+
+    #[test]
+    fn free_text_is_trimmed_and_capped() {
+        let free_text = spec(QuestionType::FreeText);
+        assert_eq!(free_text.check_ballot(&text("  hello  "), &[]).unwrap(), text("hello"));
+        assert_eq!(error_code(free_text.check_ballot(&text("   "), &[])), "invalid_ballot");
+
+        let longest = "é".repeat(MAX_ANSWER_LEN);
+        assert!(free_text.check_ballot(&text(&longest), &[]).is_ok());
+        let too_long = "é".repeat(MAX_ANSWER_LEN + 1);
+        assert_eq!(error_code(free_text.check_ballot(&text(&too_long), &[])), "invalid_ballot");
+    }
+
+    #[test]
+    fn ranked_ballots_score_their_first_preference_only() {
+        let ballot = picks(&[12, 10, 11]);
+        assert_eq!(spec(QuestionType::Ranked).scored_options(&ballot), [12]);
+        assert_eq!(spec(QuestionType::Multi).scored_options(&ballot), [12, 10, 11]);
+        assert!(spec(QuestionType::Ranked).scored_options(&picks(&[])).is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{ready, Ready};
+use std::str::FromStr;
+
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::MySqlArguments;
+use sqlx::query::Query;
+use sqlx::{MySql, MySqlPool, Row};
+
+use crate::config::AuthUser;
+use crate::errors::AppError;
+use crate::hash_token;
+
+// Site wide role that may moderate every poll
+pub const ADMIN_ROLE: &str = "admin";
+
+// A user's role on one poll, ordered from least to most privileged so a guard
+// can ask for "at least" a role
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PollRole {
+    Viewer,
+    Voter,
+    Editor,
+    Owner,
+}
+
+impl PollRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PollRole::Viewer => "viewer",
+            PollRole::Voter => "voter",
+            PollRole::Editor => "editor",
+            PollRole::Owner => "owner",
+        }
+    }
+}
+
+impl fmt::Display for PollRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PollRole {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "viewer" => Ok(PollRole::Viewer),
+            "voter" => Ok(PollRole::Voter),
+            "editor" => Ok(PollRole::Editor),
+            "owner" => Ok(PollRole::Owner),
+            _ => Err(AppError::bad_request(
+                "invalid_role",
+                "Role must be one of owner, editor, voter or viewer.",
+            )),
+        }
+    }
+}
+
+// A user's role in an organization. Admins manage members and invitations;
+// the owner can't be removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrgRole {
+    Member,
+    Admin,
+    Owner,
+}
+
+impl OrgRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrgRole::Member => "member",
+            OrgRole::Admin => "admin",
+            OrgRole::Owner => "owner",
+        }
+    }
+}
+
+impl fmt::Display for OrgRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OrgRole {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "member" => Ok(OrgRole::Member),
+            "admin" => Ok(OrgRole::Admin),
+            "owner" => Ok(OrgRole::Owner),
+            _ => Err(AppError::bad_request(
+                "invalid_role",
+                "Role must be one of owner, admin or member.",
+            )),
+        }
+    }
+}
+
+// True when `email` holds the site wide admin role. Checked against the database
+// rather than the token so revoking admin takes effect immediately.
+pub async fn is_admin(email: &str, pool: &MySqlPool) -> Result<bool, AppError> {
+    let row = sqlx::query(
+        "SELECT EXISTS(SELECT 1 FROM user_roles WHERE user_email = ? AND role = ?) AS is_admin",
+    )
+    .bind(email)
+    .bind(ADMIN_ROLE)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row.get("is_admin"))
+}
+
+pub async fn require_admin(user: &AuthUser, pool: &MySqlPool) -> Result<(), AppError> {
+    if is_admin(&user.email, pool).await? {
+        Ok(())
+    } else {
+        Err(AppError::forbidden("admin_required", "This action needs the admin role."))
+    }
+}
+
+// Who may see a poll at all, on top of organization membership
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    Public,
+    Unlisted,
+    Private,
+    InviteOnly,
+}
+
+impl Visibility {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+            Visibility::InviteOnly => "invite_only",
+        }
+    }
+}
+
+impl FromStr for Visibility {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "public" => Ok(Visibility::Public),
+            "unlisted" => Ok(Visibility::Unlisted),
+            "private" => Ok(Visibility::Private),
+            "invite_only" => Ok(Visibility::InviteOnly),
+            _ => Err(AppError::bad_request(
+                "invalid_visibility",
+                "Visibility must be one of public, unlisted, private or invite_only.",
+            )),
+        }
+    }
+}
+
+// Secret from an unlisted poll's share link, sent as the `X-Share-Token`
+// header or the `share` query parameter
+#[derive(Debug, Clone)]
+pub struct ShareToken(pub Option<String>);
+
+impl ShareToken {
+    pub fn as_deref(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+impl FromRequest for ShareToken {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = req
+            .headers()
+            .get("X-Share-Token")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+            .or_else(|| {
+                web::Query::<HashMap<String, String>>::from_query(req.query_string())
+                    .ok()
+                    .and_then(|query| query.get("share").cloned())
+            });
+        ready(Ok(ShareToken(token)))
+    }
+}
+
+// The caller's effective role on `poll_id`, or None when the poll is hidden:
+// - site admins, and owners or admins of the poll's organization, act as owners
+// - organization polls are hidden from non-members, even ones holding a poll
+//   role from before they left the organization
+// - otherwise an explicit poll role applies
+// - then visibility decides: public polls are open, unlisted ones need the
+//   share token, invite-only ones an entry on the invite list, and private
+//   ones an explicit role
+// Signed-in users get voter, anonymous callers (`user` None) only viewer.
+// Fails with 404 when the poll doesn't exist.
+pub async fn poll_role(
+    user: Option<&AuthUser>,
+    poll_id: i64,
+    share_token: Option<&str>,
+    pool: &MySqlPool,
+) -> Result<Option<PollRole>, AppError> {
+    let email = user.map(|user| user.email.as_str());
+    let row = sqlx::query(
+        r#"
+        SELECT
+            p.organization_id, p.visibility, p.share_token_hash,
+            (SELECT role FROM poll_roles WHERE poll_id = p.id AND user_email = ?) AS role,
+            (SELECT role FROM organization_members
+             WHERE organization_id = p.organization_id AND user_email = ?) AS org_role,
+            EXISTS(SELECT 1 FROM poll_invites WHERE poll_id = p.id AND email = ?) AS invited,
+            EXISTS(SELECT 1 FROM user_roles WHERE user_email = ? AND role = ?) AS is_admin
+        FROM polls p
+        WHERE p.id = ?
+        "#,
+    )
+    .bind(email)
+    .bind(email)
+    .bind(email)
+    .bind(email)
+    .bind(ADMIN_ROLE)
+    .bind(poll_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::not_found("poll_not_found", "Poll not found."))?;
+
+    if row.get::<bool, _>("is_admin") {
+        return Ok(Some(PollRole::Owner));
+    }
+    let org_role = row
+        .get::<Option<String>, _>("org_role")
+        .map(|role| role.parse::<OrgRole>())
+        .transpose()?;
+    if matches!(org_role, Some(OrgRole::Admin | OrgRole::Owner)) {
+        return Ok(Some(PollRole::Owner));
+    }
+    if row.get::<Option<i64>, _>("organization_id").is_some() && org_role.is_none() {
+        return Ok(None);
+    }
+    if let Some(role) = row.get::<Option<String>, _>("role") {
+        return role.parse().map(Some);
+    }
+
+    let default_role = if user.is_some() { PollRole::Voter } else { PollRole::Viewer };
+    let visible = match row.get::<String, _>("visibility").parse()? {
+        Visibility::Public => true,
+        Visibility::Unlisted => {
+            let share_hash: Option<String> = row.get("share_token_hash");
+            match (share_token, share_hash) {
+                (Some(token), Some(hash)) => hash_token(token) == hash,
+                _ => false,
+            }
+        }
+        Visibility::InviteOnly => row.get::<bool, _>("invited"),
+        Visibility::Private => false,
+    };
+    Ok(visible.then_some(default_role))
+}
+
+// SQL form of `poll_role` for listings: true when the poll aliased `p` is visible
+// to the caller. Unlisted polls need the share token, which a listing never
+// has, so they only show up for callers holding a role. Bind the caller with
+// `bind_visible_poll`.
+pub const VISIBLE_POLL: &str = r#"(
+    EXISTS(SELECT 1 FROM user_roles WHERE user_email = ? AND role = ?)
+    OR EXISTS(SELECT 1 FROM organization_members
+              WHERE organization_id = p.organization_id AND user_email = ?
+                AND role IN ('owner', 'admin'))
+    OR ((p.organization_id IS NULL
+         OR EXISTS(SELECT 1 FROM organization_members
+                   WHERE organization_id = p.organization_id AND user_email = ?))
+        AND (EXISTS(SELECT 1 FROM poll_roles WHERE poll_id = p.id AND user_email = ?)
+             OR p.visibility = 'public'
+             OR (p.visibility = 'invite_only'
+                 AND EXISTS(SELECT 1 FROM poll_invites WHERE poll_id = p.id AND email = ?)))))"#;
+
+pub fn bind_visible_poll<'q>(
+    query: Query<'q, MySql, MySqlArguments>,
+    email: &'q str,
+) -> Query<'q, MySql, MySqlArguments> {
+    query
+        .bind(email)
+        .bind(ADMIN_ROLE)
+        .bind(email)
+        .bind(email)
+        .bind(email)
+        .bind(email)
+}
+
+// Guard for poll routes that may be reached anonymously or through a share link:
+// 404 when the poll is hidden from the caller, 401 when signing in would be
+// enough, 403 when the caller's role is too low
+pub async fn require_poll_access(
+    user: Option<&AuthUser>,
+    share_token: Option<&str>,
+    poll_id: i64,
+    required: PollRole,
+    pool: &MySqlPool,
+) -> Result<PollRole, AppError> {
+    let role = poll_role(user, poll_id, share_token, pool)
+        .await?
+        .ok_or_else(|| AppError::not_found("poll_not_found", "Poll not found."))?;
+    if role < required {
+        if user.is_none() {
+            return Err(AppError::unauthorized("unauthenticated", "Authentication required."));
+        }
+        return Err(AppError::forbidden(
+            "insufficient_role",
+            format!("This action needs the {} role on this poll.", required),
+        ));
+    }
+    Ok(role)
+}
+
+// Guard for poll routes: fail with 403 unless the caller has at least `required`
+pub async fn require_poll_role(
+    user: &AuthUser,
+    poll_id: i64,
+    required: PollRole,
+    pool: &MySqlPool,
+) -> Result<PollRole, AppError> {
+    require_poll_access(Some(user), None, poll_id, required, pool).await
+}
+
+// The caller's role in `organization_id`; site admins act as owners.
+// Fails with 404 for non-members so organizations can't be probed.
+pub async fn org_role(
+    user: &AuthUser,
+    organization_id: i64,
+    pool: &MySqlPool,
+) -> Result<OrgRole, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+            (SELECT role FROM organization_members
+             WHERE organization_id = o.id AND user_email = ?) AS role,
+            EXISTS(SELECT 1 FROM user_roles WHERE user_email = ? AND role = ?) AS is_admin
+        FROM organizations o
+        WHERE o.id = ?
+        "#,
+    )
+    .bind(&user.email)
+    .bind(&user.email)
+    .bind(ADMIN_ROLE)
+    .bind(organization_id)
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some(row) if row.get::<bool, _>("is_admin") => Ok(OrgRole::Owner),
+        Some(row) => match row.get::<Option<String>, _>("role") {
+            Some(role) => role.parse(),
+            None => Err(AppError::not_found("organization_not_found", "Organization not found.")),
+        },
+        None => Err(AppError::not_found("organization_not_found", "Organization not found.")),
+    }
+}
+
+// Guard for organization routes: fail with 403 unless the caller has at least `required`
+pub async fn require_org_role(
+    user: &AuthUser,
+    organization_id: i64,
+    required: OrgRole,
+    pool: &MySqlPool,
+) -> Result<OrgRole, AppError> {
+    let role = org_role(user, organization_id, pool).await?;
+    if role < required {
+        return Err(AppError::forbidden(
+            "insufficient_role",
+            format!("This action needs the {} role in this organization.", required),
+        ));
+    }
+    Ok(role)
+}
+
+// Grant the admin role to the configured `access.admin_emails` at startup
+pub async fn seed_admins(admin_emails: &[String], pool: &MySqlPool) -> Result<(), sqlx::Error> {
+    for email in admin_emails {
+        sqlx::query(
+            r#"
+            INSERT IGNORE INTO user_roles (user_email, role, granted_by, created_at)
+            VALUES (?, ?, 'config', UTC_TIMESTAMP())
+            "#,
+        )
+        .bind(email)
+        .bind(ADMIN_ROLE)
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use super::{scheduled_transition, transition_poll, PollStatus};
+use crate::errors::AppError;
+use crate::lobby::Lobby;
+use crate::messages::NotifyPollId;
+use actix::{Actor, ActorFutureExt, Addr, AsyncContext, Context, ContextFutureSpawner, WrapFuture};
+use sqlx::{MySqlPool, Row};
+use std::time::Duration;
+
+// Opens scheduled polls at their opens_at, closes polls at their closes_at and
+// tells the lobby about each, so connected clients refresh without anyone pressing a button
+pub struct PollScheduler {
+    pool: MySqlPool,
+    lobby: Addr<Lobby>,
+    interval: Duration,
+}
+
+impl PollScheduler {
+    pub fn new(pool: MySqlPool, lobby: Addr<Lobby>, interval: Duration) -> PollScheduler {
+        PollScheduler {
+            pool,
+            lobby,
+            interval,
+        }
+    }
+
+    fn tick(&mut self, ctx: &mut Context<Self>) {
+        let pool = self.pool.clone();
+        async move { run_schedule(&pool).await }
+            .into_actor(self)
+            .map(|poll_ids, act, _| {
+                for poll_id in poll_ids {
+                    act.lobby.do_send(NotifyPollId { poll_id });
+                }
+            })
+            // Don't start another check before this one finished
+            .wait(ctx);
+    }
+}
+
+impl Actor for PollScheduler {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        println!("Poll scheduler running every {:?}", self.interval);
+        ctx.run_interval(self.interval, |act, ctx| act.tick(ctx));
+    }
+}
+
+// Polls that are due: scheduled ones past their opens_at and running ones past
+// their closes_at. `scheduled_transition` decides where each one goes.
+const DUE: &str = r#"
+    SELECT id FROM polls
+    WHERE (status = 'scheduled' AND opens_at <= UTC_TIMESTAMP())
+       OR (status IN ('scheduled', 'open', 'paused') AND closes_at <= UTC_TIMESTAMP())
+"#;
+
+// Open and close every poll that is due. Returns the polls that changed; a poll
+// that fails is logged and skipped so the others are still moved and announced.
+async fn run_schedule(pool: &MySqlPool) -> Vec<i64> {
+    let due: Vec<i64> = match sqlx::query(DUE).fetch_all(pool).await {
+        Ok(rows) => rows.iter().map(|row| row.get("id")).collect(),
+        Err(err) => {
+            eprintln!("Poll scheduler failed to list due polls: {}", err);
+            return Vec::new();
+        }
+    };
+
+    let mut changed = Vec::new();
+    for poll_id in due {
+        match apply_schedule(pool, poll_id).await {
+            Ok(Some(to)) => {
+                println!("Poll {} is now {} on schedule", poll_id, to);
+                changed.push(poll_id);
+            }
+            Ok(None) => {}
+            Err(err) => eprintln!("Poll scheduler failed to move poll {}: {}", poll_id, err),
+        }
+    }
+    changed
+}
+
+// Move one poll to wherever its schedule says it should be, rechecked under
+// the row lock since an editor may have moved it since it was listed
+async fn apply_schedule(pool: &MySqlPool, poll_id: i64) -> Result<Option<PollStatus>, AppError> {
+    let mut tx = pool.begin().await?;
+    let poll = sqlx::query(
+        r#"
+        SELECT status, opens_at, closes_at, UTC_TIMESTAMP() AS now
+        FROM polls
+        WHERE id = ?
+        FOR UPDATE
+        "#,
+    )
+    .bind(poll_id)
+    .fetch_optional(&mut tx)
+    .await?;
+    let poll = match poll {
+        Some(poll) => poll,
+        None => return Ok(None),
+    };
+
+    let to = scheduled_transition(
+        poll.get::<String, _>("status").parse()?,
+        poll.get("opens_at"),
+        poll.get("closes_at"),
+        poll.get("now"),
+    );
+    if let Some(to) = to {
+        transition_poll(&mut tx, poll_id, to, None, None).await?;
+        tx.commit().await?;
+    }
+    Ok(to)
+}
@@ -0,0 +1,283 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+// Votes held by one option in an instant-runoff round
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundTally {
+    pub option_id: i64,
+    pub votes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunoffRound {
+    pub round: usize,
+    pub tallies: Vec<RoundTally>,
+    // Ballots whose every ranked option has been eliminated
+    pub exhausted: usize,
+    // Option dropped at the end of this round, None in the final round
+    pub eliminated: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunoffResult {
+    pub winner: Option<i64>,
+    pub rounds: Vec<RunoffRound>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BordaScore {
+    pub option_id: i64,
+    pub points: usize,
+}
+
+// Instant-runoff over `ballots` (option ids, most preferred first) between
+// `options`. Each round every ballot counts for its highest ranked option still
+// in the race; an option with a majority of the counted ballots wins, otherwise
+// the weakest option is eliminated. Ties for elimination go to the option that
+// was weaker in the earliest round that separates them, then to the later option.
+pub fn instant_runoff(options: &[i64], ballots: &[Vec<i64>]) -> RunoffResult {
+    let mut continuing: Vec<i64> = options.to_vec();
+    let mut rounds: Vec<RunoffRound> = Vec::new();
+    let mut history: Vec<HashMap<i64, usize>> = Vec::new();
+
+    while !continuing.is_empty() {
+        let mut counts: HashMap<i64, usize> = continuing.iter().map(|id| (*id, 0)).collect();
+        let mut exhausted = 0;
+        for ballot in ballots {
+            match ballot.iter().find(|id| counts.contains_key(id)) {
+                Some(id) => *counts.get_mut(id).unwrap() += 1,
+                None => exhausted += 1,
+            }
+        }
+
+        let counted = ballots.len() - exhausted;
+        let mut tallies: Vec<RoundTally> = continuing
+            .iter()
+            .map(|id| RoundTally {
+                option_id: *id,
+                votes: counts[id],
+            })
+            .collect();
+        tallies.sort_by(|a, b| b.votes.cmp(&a.votes));
+
+        let leader = tallies.first().map(|tally| (tally.option_id, tally.votes));
+        let majority = matches!(leader, Some((_, votes)) if counted > 0 && votes * 2 > counted);
+        if majority || continuing.len() == 1 || counted == 0 {
+            rounds.push(RunoffRound {
+                round: rounds.len() + 1,
+                tallies,
+                exhausted,
+                eliminated: None,
+            });
+            // Without any counted ballot there is nobody to pick
+            let winner = if counted == 0 { None } else { leader.map(|(id, _)| id) };
+            return RunoffResult { winner, rounds };
+        }
+
+        history.push(counts);
+        let eliminated = weakest(&continuing, &history);
+        continuing.retain(|id| *id != eliminated);
+        rounds.push(RunoffRound {
+            round: rounds.len() + 1,
+            tallies,
+            exhausted,
+            eliminated: Some(eliminated),
+        });
+    }
+
+    RunoffResult {
+        winner: None,
+        rounds,
+    }
+}
+
+// The option to eliminate: fewest votes in the latest round, ties broken by
+// the earliest round that separates the tied options, then the later option
+fn weakest(continuing: &[i64], history: &[HashMap<i64, usize>]) -> i64 {
+    let latest = history.last().expect("at least one round was counted");
+    let fewest = continuing.iter().map(|id| latest[id]).min().unwrap_or(0);
+    let mut tied: Vec<i64> = continuing
+        .iter()
+        .copied()
+        .filter(|id| latest[id] == fewest)
+        .collect();
+
+    for counts in history {
+        if tied.len() == 1 {
+            break;
+        }
+        let fewest = tied.iter().map(|id| counts[id]).min().unwrap_or(0);
+        tied.retain(|id| counts[id] == fewest);
+    }
+    tied.into_iter().max().expect("a continuing option")
+}
+
+// Borda count: with n options a ballot gives n - 1 points to its first
+// preference, n - 2 to the second and so on; unranked options get nothing.
+// Sorted by points, then by option id.
+pub fn borda_count(options: &[i64], ballots: &[Vec<i64>]) -> Vec<BordaScore> {
+    let known: HashSet<i64> = options.iter().copied().collect();
+    let mut points: HashMap<i64, usize> = options.iter().map(|id| (*id, 0)).collect();
+    let n = options.len();
+
+    for ballot in ballots {
+        // Options removed after the ballot was cast don't take up a rank
+        let ranked = ballot.iter().filter(|id| known.contains(id));
+        for (rank, id) in ranked.enumerate() {
+            *points.get_mut(id).unwrap() += n.saturating_sub(rank + 1);
+        }
+    }
+
+    let mut scores: Vec<BordaScore> = options
+        .iter()
+        .map(|id| BordaScore {
+            option_id: *id,
+            points: points[id],
+        })
+        .collect();
+    scores.sort_by(|a, b| b.points.cmp(&a.points).then(a.option_id.cmp(&b.option_id)));
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ballots(ballots: &[&[i64]]) -> Vec<Vec<i64>> {
+        ballots.iter().map(|ballot| ballot.to_vec()).collect()
+    }
+
+    // `n` copies of `ballot`
+    fn repeat(n: usize, ballot: &[i64]) -> Vec<Vec<i64>> {
+        vec![ballot.to_vec(); n]
+    }
+
+    fn tallies(round: &RunoffRound) -> Vec<(i64, usize)> {
+        round
+            .tallies
+            .iter()
+            .map(|tally| (tally.option_id, tally.votes))
+            .collect()
+    }
+
+    fn eliminated(result: &RunoffResult) -> Vec<Option<i64>> {
+        result.rounds.iter().map(|round| round.eliminated).collect()
+    }
+
+    fn points(scores: &[BordaScore]) -> Vec<(i64, usize)> {
+        scores.iter().map(|score| (score.option_id, score.points)).collect()
+    }
+
+    #[test]
+    fn first_round_majority_wins_outright() {
+        let result = instant_runoff(&[1, 2, 3], &ballots(&[&[1], &[1, 2], &[2]]));
+        assert_eq!(result.winner, Some(1));
+        assert_eq!(eliminated(&result), [None]);
+        assert_eq!(tallies(&result.rounds[0]), [(1, 2), (2, 1), (3, 0)]);
+    }
+
+    #[test]
+    fn eliminated_ballots_transfer_to_their_next_preference() {
+        // 1: 2, 2: 2, 3: 1 -> 3 out, its ballot moves to 2, which then has 3 of 5
+        let result =
+            instant_runoff(&[1, 2, 3], &ballots(&[&[1], &[1], &[2], &[2], &[3, 2]]));
+        assert_eq!(result.winner, Some(2));
+        assert_eq!(eliminated(&result), [Some(3), None]);
+        assert_eq!(tallies(&result.rounds[1]), [(2, 3), (1, 2)]);
+        assert_eq!(result.rounds[1].exhausted, 0);
+    }
+
+    #[test]
+    fn majority_is_of_the_ballots_still_counted() {
+        // 1: 3, 2: 2, 3: 1 of 6 is no majority. Once 3 is out its ballot is
+        // exhausted and 3 of the 5 remaining ballots is.
+        let mut cast = repeat(3, &[1]);
+        cast.extend(repeat(2, &[2]));
+        cast.push(vec![3]);
+        let result = instant_runoff(&[1, 2, 3], &cast);
+        assert_eq!(result.winner, Some(1));
+        assert_eq!(eliminated(&result), [Some(3), None]);
+        assert_eq!(result.rounds[0].exhausted, 0);
+        assert_eq!(result.rounds[1].exhausted, 1);
+        assert_eq!(tallies(&result.rounds[1]), [(1, 3), (2, 2)]);
+    }
+
+    #[test]
+    fn elimination_ties_go_to_the_option_weaker_in_an_earlier_round() {
+        // Round 1: 1: 4, 3: 3, 2: 2, 4: 1 -> 4 out, its ballot moves to 2.
+        // Round 2: 2 and 3 tie on 3, but 2 had fewer in round 1, so 2 goes
+        // even though the last-resort rule would drop the later option 3.
+        let mut cast = repeat(4, &[1]);
+        cast.extend(repeat(3, &[3]));
+        cast.extend(repeat(2, &[2]));
+        cast.push(vec![4, 2]);
+        let result = instant_runoff(&[1, 2, 3, 4], &cast);
+        assert_eq!(eliminated(&result), [Some(4), Some(2), None]);
+        assert_eq!(tallies(&result.rounds[1]), [(1, 4), (2, 3), (3, 3)]);
+        // Ballots for 2 and 4 have nowhere left to go: 4 of 7 counted is a majority
+        assert_eq!(result.rounds[2].exhausted, 3);
+        assert_eq!(result.winner, Some(1));
+    }
+
+    #[test]
+    fn ties_level_in_every_round_drop_the_later_option() {
+        // Round 1: 1: 2, 3: 2, 2: 1 -> 2 out and its ballot is exhausted.
+        // Round 2: 1 and 3 tie 2-2 now and in round 1, so the later option 3 goes.
+        let cast = ballots(&[&[1], &[1], &[2], &[3], &[3, 1]]);
+        let result = instant_runoff(&[1, 2, 3], &cast);
+        assert_eq!(eliminated(&result), [Some(2), Some(3), None]);
+        assert_eq!(result.rounds[1].exhausted, 1);
+        assert_eq!(tallies(&result.rounds[2]), [(1, 3)]);
+        assert_eq!(result.rounds[2].exhausted, 2);
+        assert_eq!(result.winner, Some(1));
+    }
+
+    #[test]
+    fn no_counted_ballots_means_no_winner() {
+        let result = instant_runoff(&[1, 2], &[]);
+        assert_eq!(result.winner, None);
+        assert_eq!(eliminated(&result), [None]);
+        assert_eq!(tallies(&result.rounds[0]), [(1, 0), (2, 0)]);
+
+        // Empty ballots, or ballots for removed options, are exhausted from the start
+        let result = instant_runoff(&[1, 2], &ballots(&[&[], &[9]]));
+        assert_eq!(result.winner, None);
+        assert_eq!(result.rounds.len(), 1);
+        assert_eq!(result.rounds[0].exhausted, 2);
+    }
+
+    #[test]
+    fn no_options_means_no_rounds() {
+        let result = instant_runoff(&[], &ballots(&[&[1]]));
+        assert_eq!(result.winner, None);
+        assert!(result.rounds.is_empty());
+    }
+
+    #[test]
+    fn a_lone_option_wins_with_any_ballot() {
+        let result = instant_runoff(&[5], &ballots(&[&[5], &[]]));
+        assert_eq!(result.winner, Some(5));
+        assert_eq!(eliminated(&result), [None]);
+    }
+
+    #[test]
+    fn borda_gives_n_minus_one_points_down_to_zero() {
+        // n = 3: [1, 2, 3] gives 1: 2, 2: 1; [2, 1] gives 2: 2, 1: 1; [3] gives 3: 2
+        let scores = borda_count(&[1, 2, 3], &ballots(&[&[1, 2, 3], &[2, 1], &[3]]));
+        assert_eq!(points(&scores), [(1, 3), (2, 3), (3, 2)]);
+    }
+
+    #[test]
+    fn borda_skips_removed_options_without_losing_a_rank() {
+        let scores = borda_count(&[1, 2, 3], &ballots(&[&[9, 2, 1]]));
+        assert_eq!(points(&scores), [(2, 2), (1, 1), (3, 0)]);
+    }
+
+    #[test]
+    fn borda_handles_empty_input() {
+        assert_eq!(points(&borda_count(&[1, 2], &[])), [(1, 0), (2, 0)]);
+        assert_eq!(points(&borda_count(&[1, 2], &ballots(&[&[]]))), [(1, 0), (2, 0)]);
+        assert!(borda_count(&[], &ballots(&[&[1]])).is_empty());
+    }
+}
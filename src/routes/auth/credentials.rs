@@ -0,0 +1,183 @@
+use actix_web::{
+    delete, get, patch, post,
+    web::{self, Data},
+    HttpResponse,
+};
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use webauthn_rs::prelude::{CreationChallengeResponse, Passkey, RegisterPublicKeyCredential};
+use webauthn_rs::Webauthn;
+
+use crate::config::{require_resident_key, AuthUser, Settings};
+use crate::errors::AppError;
+use crate::{
+    delete_credential, get_user_credentials, get_user_handle,
+    list_user_credentials, rename_credential, store_registration_state, store_user_credential,
+    take_passkey_registration, CeremonyChallenge,
+};
+
+const MAX_NICKNAME_LEN: usize = 100;
+
+#[derive(Deserialize)]
+pub struct FinishEnrollmentRequest {
+    pub ceremony_id: String, // Returned by the matching register/start route
+    pub public_key_credential: RegisterPublicKeyCredential,
+    pub nickname: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RenameCredentialRequest {
+    nickname: String,
+}
+
+fn validate_nickname(nickname: &str) -> Result<&str, AppError> {
+    let nickname = nickname.trim();
+    if nickname.is_empty() || nickname.chars().count() > MAX_NICKNAME_LEN {
+        return Err(AppError::bad_request(
+            "invalid_nickname",
+            format!("Nickname must be 1 to {} characters.", MAX_NICKNAME_LEN),
+        ));
+    }
+    Ok(nickname)
+}
+
+// Issue a registration challenge for another passkey on the existing account `email`.
+// Shared by signed-in enrollment and account recovery.
+pub async fn begin_credential_enrollment(
+    email: &str,
+    webauthn: &Webauthn,
+    settings: &Settings,
+    pool: &MySqlPool,
+) -> Result<CeremonyChallenge<CreationChallengeResponse>, AppError> {
+    let display_name = sqlx::query_scalar::<_, String>("SELECT display_name FROM users WHERE email = ?")
+        .bind(email)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::not_found("user_not_found", "User not found."))?;
+
+    // Reuse the account's user handle and exclude passkeys it already has
+    let user_handle = get_user_handle(email, pool).await?;
+    let exclude_credentials = get_user_credentials(email, pool).await;
+
+    let (mut challenge_response, passkey_registration) = webauthn
+        .start_passkey_registration(user_handle, email, &display_name, exclude_credentials)
+        .map_err(|err| AppError::internal(format!("Failed to start registration: {}", err)))?;
+    require_resident_key(&mut challenge_response);
+
+    let ceremony_id = store_registration_state(
+        email,
+        &passkey_registration,
+        settings.webauthn.timeout_secs,
+        pool,
+    )
+    .await?;
+
+    Ok(CeremonyChallenge {
+        ceremony_id,
+        challenge: challenge_response,
+    })
+}
+
+// Check the authenticator's answer to an enrollment started by
+// `begin_credential_enrollment`. Returns the new passkey and its nickname
+// without storing anything.
+pub async fn verify_credential_enrollment<'a>(
+    email: &str,
+    req_body: &'a FinishEnrollmentRequest,
+    webauthn: &Webauthn,
+    pool: &MySqlPool,
+) -> Result<(Passkey, Option<&'a str>), AppError> {
+    let nickname = req_body
+        .nickname
+        .as_deref()
+        .map(validate_nickname)
+        .transpose()?;
+
+    let passkey_registration = take_passkey_registration(&req_body.ceremony_id, email, pool).await?;
+
+    let passkey = webauthn
+        .finish_passkey_registration(&req_body.public_key_credential, &passkey_registration)
+        .map_err(|_| {
+            AppError::unauthorized("registration_failed", "Failed to finish registration.")
+        })?;
+
+    Ok((passkey, nickname))
+}
+
+// Finish an enrollment started by `begin_credential_enrollment` and store the passkey
+pub async fn complete_credential_enrollment(
+    email: &str,
+    req_body: &FinishEnrollmentRequest,
+    webauthn: &Webauthn,
+    pool: &MySqlPool,
+) -> Result<(), AppError> {
+    let (passkey, nickname) = verify_credential_enrollment(email, req_body, webauthn, pool).await?;
+    let mut conn = pool.acquire().await?;
+    store_user_credential(email, &passkey, nickname, &mut conn).await
+}
+
+// Begin adding another passkey to the signed-in account
+#[post("/credentials/register/start")]
+pub async fn start_credential_enrollment(
+    pool: web::Data<MySqlPool>,
+    webauthn: Data<Webauthn>,
+    settings: Data<Settings>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /credentials/register/start");
+    let challenge = begin_credential_enrollment(&user.email, &webauthn, &settings, &pool).await?;
+    Ok(HttpResponse::Ok().json(challenge))
+}
+
+// Complete the enrollment started above and store the new passkey
+#[post("/credentials/register/finish")]
+pub async fn finish_credential_enrollment(
+    pool: web::Data<MySqlPool>,
+    webauthn: Data<Webauthn>,
+    req_body: web::Json<FinishEnrollmentRequest>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /credentials/register/finish");
+    complete_credential_enrollment(&user.email, &req_body, &webauthn, &pool).await?;
+    Ok(HttpResponse::Created().json("Passkey added."))
+}
+
+// List the passkeys on the signed-in account
+#[get("/credentials")]
+pub async fn list_credentials(
+    pool: web::Data<MySqlPool>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("GET /credentials");
+    let credentials = list_user_credentials(&user.email, &pool).await?;
+    Ok(HttpResponse::Ok().json(credentials))
+}
+
+#[patch("/credentials/{credential_id}")]
+pub async fn update_credential(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<i64>,
+    req_body: web::Json<RenameCredentialRequest>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let credential_id = path.into_inner();
+    println!("PATCH /credentials/{}", credential_id);
+    let nickname = validate_nickname(&req_body.nickname)?;
+
+    rename_credential(&user.email, credential_id, nickname, &pool).await?;
+    Ok(HttpResponse::Ok().json("Passkey renamed."))
+}
+
+// Revoke a passkey; the last one on the account is refused
+#[delete("/credentials/{credential_id}")]
+pub async fn revoke_credential(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<i64>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let credential_id = path.into_inner();
+    println!("DELETE /credentials/{}", credential_id);
+
+    delete_credential(&user.email, credential_id, &pool).await?;
+    Ok(HttpResponse::Ok().json("Passkey removed."))
+}
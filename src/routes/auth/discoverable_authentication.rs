@@ -0,0 +1,88 @@
+use actix_web::{
+    post,
+    web::{self, Data},
+    HttpRequest, HttpResponse,
+};
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use webauthn_rs::prelude::{DiscoverableAuthentication, DiscoverableKey, PublicKeyCredential};
+use webauthn_rs::Webauthn;
+
+use super::start_login_session;
+use crate::config::Settings;
+use crate::errors::AppError;
+use crate::{
+    get_user_by_handle, get_user_credentials_passkeys, store_ceremony_state, take_ceremony_state,
+    update_credential_counter, CeremonyChallenge, CeremonyKind,
+};
+
+#[derive(Deserialize)]
+struct FinishDiscoverableRequest {
+    ceremony_id: String, // Returned by /login/discoverable/start
+    public_key_credential: PublicKeyCredential,
+}
+
+// Usernameless login: the challenge names no credentials, so the browser offers
+// any passkey it holds for this site
+#[post("/login/discoverable/start")]
+pub async fn start_discoverable_authentication(
+    pool: web::Data<MySqlPool>,
+    webauthn: Data<Webauthn>,
+    settings: Data<Settings>,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /login/discoverable/start");
+    let (challenge_response, auth_state) = webauthn
+        .start_discoverable_authentication()
+        .map_err(|err| AppError::internal(format!("Failed to start authentication: {}", err)))?;
+
+    // Not bound to a user yet; the user is only known from the response
+    let ceremony_id = store_ceremony_state(
+        CeremonyKind::Discoverable,
+        None,
+        &auth_state,
+        settings.webauthn.timeout_secs,
+        &pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(CeremonyChallenge {
+        ceremony_id,
+        challenge: challenge_response,
+    }))
+}
+
+#[post("/login/discoverable/finish")]
+pub async fn finish_discoverable_authentication(
+    pool: web::Data<MySqlPool>,
+    webauthn: Data<Webauthn>,
+    settings: Data<Settings>,
+    req_body: web::Json<FinishDiscoverableRequest>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /login/discoverable/finish");
+    let public_key_credential = &req_body.public_key_credential;
+    let auth_state: DiscoverableAuthentication =
+        take_ceremony_state(&req_body.ceremony_id, CeremonyKind::Discoverable, None, &pool).await?;
+
+    let failed =
+        || AppError::unauthorized("authentication_failed", "Failed to finish authentication.");
+
+    // The authenticator returns the user handle it was registered with
+    let (user_handle, _) = webauthn
+        .identify_discoverable_authentication(public_key_credential)
+        .map_err(|_| failed())?;
+    let email = get_user_by_handle(user_handle, &pool).await?;
+
+    let passkeys = get_user_credentials_passkeys(&email, &pool).await?;
+    let keys: Vec<DiscoverableKey> = passkeys.iter().map(DiscoverableKey::from).collect();
+    let auth_result = webauthn
+        .finish_discoverable_authentication(public_key_credential, auth_state, &keys)
+        .map_err(|_| failed())?;
+
+    update_credential_counter(&auth_result, settings.webauthn.counter_policy, &pool).await?;
+
+    // The client never typed an email, so tell it who signed in
+    let mut response = start_login_session(&email, &settings, &request, &pool).await?;
+    response["email"] = serde_json::Value::String(email);
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -1,66 +1,92 @@
 use actix_web::{
     post,
     web::{self, Data},
-    HttpResponse, Responder,
+    HttpRequest, HttpResponse,
 };
-use jsonwebtoken::{encode, EncodingKey, Header};
-use serde::{Deserialize, Serialize};
+use actix_web::http::header::USER_AGENT;
+use serde::Deserialize;
 use sqlx::MySqlPool;
 use uuid::Uuid;
-use webauthn_rs::prelude::{
-    Base64UrlSafeData, CreationChallengeResponse, CredentialID, Passkey, PublicKeyCredential,
-    RegisterPublicKeyCredential,
-};
+use webauthn_rs::Webauthn;
+use webauthn_rs::prelude::PublicKeyCredential;
 
 use crate::{
-    config::create_webauthn_instance, get_passkey_auth_state, get_passkey_registration,
-    get_user_credentials, get_user_credentials_passkeys, store_passkey_auth_state,
-    store_passkey_registration, store_user_credential, update_credential_counter,
+    config::{issue_access_token, Settings}, create_session, get_user_roles, issue_refresh_token,
+    take_passkey_auth_state, update_credential_counter, CeremonyKind,
 };
-
-#[derive(Serialize, Deserialize)]
-struct Claims {
-    sub: String,
-    exp: usize, // Expiration time in seconds
-}
+use crate::errors::AppError;
 
 #[derive(Deserialize)]
 struct FinishAuthenticationRequest {
     email: String,
+    ceremony_id: String, // Returned by /login/start
     public_key_credential: PublicKeyCredential,
 }
 
 #[post("/login/finish")]
 pub async fn finish_authentication(
     pool: web::Data<sqlx::MySqlPool>, // Your MySQL connection pool
+    webauthn: Data<Webauthn>, // Shared relying party built at startup
+    settings: Data<Settings>,
     req_body: web::Json<FinishAuthenticationRequest>,
-) -> impl Responder {
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
     println!("/POST login/finish");
-    let data = create_webauthn_instance();
+    let data = webauthn.get_ref();
     let email = &req_body.email;
     let public_key_credential = &req_body.public_key_credential;
 
-    // Retrieve the passkey authentication state from the database
-    let passkey_auth_state = get_passkey_auth_state(email, &pool).await;
+    // Retrieve (and consume) the passkey authentication state from the database
+    let passkey_auth_state =
+        take_passkey_auth_state(&req_body.ceremony_id, CeremonyKind::Authentication, email, &pool)
+            .await?;
 
     // Finish the WebAuthn authentication
-    match data.finish_passkey_authentication(public_key_credential, &passkey_auth_state) {
-        Ok(auth_result) => {
-            update_credential_counter(email, 1, &pool).await;
-            let my_claims = Claims {
-                sub: email.to_owned(),
-                exp: 10000000000, // Set expiration time here
-            };
-            let secret_key = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-            let token = encode(
-                &Header::default(),
-                &my_claims,
-                &EncodingKey::from_secret(secret_key.as_ref()), // Secret key for signing
-            )
-            .unwrap(); // Handle errors appropriately
-            HttpResponse::Ok()
-                .json(serde_json::json!({ "token": token , "message": "Authentication successful"}))
-        }
-        Err(_) => HttpResponse::InternalServerError().json("Failed to finish authentication"),
-    }
+    let auth_result = data
+        .finish_passkey_authentication(public_key_credential, &passkey_auth_state)
+        .map_err(|_| {
+            AppError::unauthorized("authentication_failed", "Failed to finish authentication.")
+        })?;
+
+    update_credential_counter(&auth_result, settings.webauthn.counter_policy, &pool).await?;
+
+    let response = start_login_session(email, &settings, &request, &pool).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Open a session for `email` after a successful passkey login and issue its tokens.
+// Shared by the email and the usernameless login flows.
+pub async fn start_login_session(
+    email: &str,
+    settings: &Settings,
+    request: &HttpRequest,
+    pool: &MySqlPool,
+) -> Result<serde_json::Value, AppError> {
+    // Each login starts a session; its id is the access token `jti` and the refresh token family
+    let session_id = Uuid::new_v4().to_string();
+    let device = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok());
+    let ip_address = request
+        .connection_info()
+        .realip_remote_addr()
+        .map(str::to_owned);
+    create_session(&session_id, email, device, ip_address.as_deref(), pool).await?;
+    let roles = get_user_roles(email, pool).await?;
+    let token = issue_access_token(&settings.jwt, email, roles, &session_id)?;
+    let refresh_token = issue_refresh_token(
+        email,
+        &session_id,
+        settings.jwt.refresh_token_ttl_secs,
+        pool,
+    )
+    .await?;
+
+    Ok(serde_json::json!({
+        "token": token,
+        "refresh_token": refresh_token,
+        "expires_in": settings.jwt.access_token_ttl_secs,
+        "message": "Authentication successful"
+    }))
 }
@@ -1,51 +1,54 @@
 use actix_web::{
     post,
     web::{self, Data},
-    HttpResponse, Responder,
-};
-use jsonwebtoken::{encode, EncodingKey, Header};
-use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
-use uuid::Uuid;
-use webauthn_rs::prelude::{
-    Base64UrlSafeData, CreationChallengeResponse, CredentialID, Passkey, PublicKeyCredential,
-    RegisterPublicKeyCredential,
+    HttpResponse,
 };
+use serde::Deserialize;
+use webauthn_rs::Webauthn;
+use webauthn_rs::prelude::RegisterPublicKeyCredential;
 
-use crate::{
-    config::create_webauthn_instance, get_passkey_auth_state, get_passkey_registration,
-    get_user_credentials, get_user_credentials_passkeys, store_passkey_auth_state,
-    store_passkey_registration, store_user_credential, update_credential_counter,
-};
+use crate::{issue_recovery_codes, store_user_credential, take_passkey_registration};
+use crate::errors::AppError;
 
 #[derive(Deserialize)]
 struct FinishRegistrationRequest {
     email: String,
+    ceremony_id: String, // Returned by /register/start
     public_key_credential: RegisterPublicKeyCredential,
+    nickname: Option<String>, // Label for the first passkey, e.g. "Phone"
 }
 
 #[post("/register/finish")]
 pub async fn finish_registration(
     pool: web::Data<sqlx::MySqlPool>, // Your MySQL connection pool
+    webauthn: Data<Webauthn>, // Shared relying party built at startup
     req_body: web::Json<FinishRegistrationRequest>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     println!("/POST register/finish");
 
-    let data = create_webauthn_instance();
+    let data = webauthn.get_ref();
     let email = &req_body.email;
     let public_key_credential = &req_body.public_key_credential;
 
-    // Retrieve the passkey registration state from the database
-    let passkey_registration = get_passkey_registration(email, &pool).await.unwrap();
+    // Retrieve (and consume) the passkey registration state from the database
+    let passkey_registration = take_passkey_registration(&req_body.ceremony_id, email, &pool).await?;
 
     // Finish the WebAuthn registration
-    match data.finish_passkey_registration(&public_key_credential, &passkey_registration) {
-        Ok(auth_result) => {
-            // Store the new credential and user
-            store_user_credential(email, &auth_result, &pool).await;
-
-            HttpResponse::Ok().json("Registration successful")
-        }
-        Err(_) => HttpResponse::InternalServerError().json("Failed to finish registration"),
-    }
+    let passkey = data
+        .finish_passkey_registration(&public_key_credential, &passkey_registration)
+        .map_err(|_| {
+            AppError::unauthorized("registration_failed", "Failed to finish registration.")
+        })?;
+
+    // Store the new credential together with its recovery codes, the only way
+    // back in if this passkey is lost; the codes are shown once and stored hashed
+    let mut tx = pool.begin().await?;
+    store_user_credential(email, &passkey, req_body.nickname.as_deref(), &mut tx).await?;
+    let recovery_codes = issue_recovery_codes(email, &mut tx).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Registration successful",
+        "recovery_codes": recovery_codes,
+    })))
 }
@@ -1,76 +1,138 @@
 use actix_web::{
     post,
     web::{self, Data},
-    HttpResponse, Responder,
-};
-use jsonwebtoken::{encode, EncodingKey, Header};
-use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
-use uuid::Uuid;
-use webauthn_rs::prelude::{
-    Base64UrlSafeData, CreationChallengeResponse, CredentialID, Passkey, PublicKeyCredential,
-    RegisterPublicKeyCredential,
+    HttpResponse,
 };
+use serde::Deserialize;
+use sqlx::{MySqlPool, Row};
+use webauthn_rs::Webauthn;
+use webauthn_rs::prelude::PublicKeyCredential;
 
-use crate::{
-    config::create_webauthn_instance, get_passkey_auth_state, get_passkey_registration,
-    get_user_credentials, get_user_credentials_passkeys, store_passkey_auth_state,
-    store_passkey_registration, store_user_credential, update_credential_counter,
-};
-
-#[derive(Serialize, Deserialize)]
-struct Claims {
-    sub: String,
-    option_id: String, // Add option_id to the claims
-    exp: usize, // Expiration time in seconds
-}
+use crate::{take_passkey_auth_state, update_credential_counter, CeremonyKind};
+use crate::config::{issue_vote_token, AuthUser, Settings};
+use crate::errors::AppError;
+use crate::polls::{load_question, require_poll_access, Ballot, PollRole, ShareToken};
+use crate::routes::polling::ensure_poll_open;
 
 #[derive(Deserialize)]
 struct FinishAuthenticationRequest {
     email: String,
+    ceremony_id: String, // Returned by /start_verification
     public_key_credential: PublicKeyCredential,
-    option_id: String, // Include option_id in the request
+    poll_id: i64,      // Poll the vote token is bound to
+    question_id: Option<i64>, // Question the ballot answers
+    ballot: Option<Ballot>,
+    option_id: Option<String>, // Shorthand for a single choice ballot; implies the question
 }
 
 #[post("/getpass")]
 pub async fn finish_verification(
     pool: web::Data<MySqlPool>, // Your MySQL connection pool
+    webauthn: Data<Webauthn>, // Shared relying party built at startup
+    settings: Data<Settings>,
     req_body: web::Json<FinishAuthenticationRequest>,
-) -> impl Responder {
-    println!("/POST login/finish");
-    
-    let data = create_webauthn_instance();
+    user: AuthUser,
+    share: ShareToken,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /getpass");
+
+    // A vote can only be verified with the caller's own passkey
+    if req_body.email != user.email {
+        return Err(AppError::forbidden(
+            "email_mismatch",
+            "Verification must use the signed-in account.",
+        ));
+    }
+
+    // Don't verify a passkey for someone who isn't allowed to vote anyway
+    require_poll_access(Some(&user), share.as_deref(), req_body.poll_id, PollRole::Voter, &pool)
+        .await?;
+
+    let data = webauthn.get_ref();
     let email = &req_body.email;
     let public_key_credential = &req_body.public_key_credential;
-    let option_id = &req_body.option_id; // Extract option_id from the request
 
-    // Retrieve the passkey authentication state from the database
-    let passkey_auth_state = get_passkey_auth_state(email, &pool).await;
+    // Retrieve (and consume) the passkey authentication state from the database
+    let passkey_auth_state =
+        take_passkey_auth_state(&req_body.ceremony_id, CeremonyKind::Verification, email, &pool)
+            .await?;
 
     // Finish the WebAuthn authentication
-    match data.finish_passkey_authentication(public_key_credential, &passkey_auth_state) {
-        Ok(auth_result) => {
-            // Optionally handle auth_result if needed
+    let auth_result = data
+        .finish_passkey_authentication(public_key_credential, &passkey_auth_state)
+        .map_err(|_| {
+            AppError::unauthorized("verification_failed", "Failed to finish authentication.")
+        })?;
 
-            // Update credential counter
-            update_credential_counter(email, 1, &pool).await;
+    // Update credential counter
+    update_credential_counter(&auth_result, settings.webauthn.counter_policy, &pool).await?;
 
-            // Create JWT claims with email and option_id
-            let my_claims = Claims {
-                sub: email.to_owned(),
-                option_id: option_id.to_owned(), // Include option_id
-                exp: 10000000000, // Set expiration time here
+    // Work out which question the ballot answers
+    let (question_id, ballot) = match (&req_body.ballot, &req_body.option_id) {
+        (Some(ballot), _) => {
+            let question_id = req_body.question_id.ok_or_else(|| {
+                AppError::bad_request("missing_question_id", "A ballot needs its question_id.")
+            })?;
+            (question_id, ballot.clone())
+        }
+        (None, Some(option_id)) => {
+            let option = sqlx::query(
+                r#"
+                SELECT po.id, po.question_id
+                FROM poll_options po
+                INNER JOIN questions q ON q.id = po.question_id
+                WHERE po.id = ? AND q.poll_id = ?
+                "#,
+            )
+            .bind(option_id)
+            .bind(req_body.poll_id)
+            .fetch_optional(pool.get_ref())
+            .await?
+            .ok_or_else(|| {
+                AppError::bad_request("invalid_option", "Invalid option for this poll.")
+            })?;
+            let ballot = Ballot {
+                option_ids: vec![option.get("id")],
+                ..Ballot::default()
             };
-            let secret_key = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-            let token = encode(
-                &Header::default(),
-                &my_claims,
-                &EncodingKey::from_secret(secret_key.as_ref()), // Secret key for signing
-            ).expect("Failed to encode token"); // Handle errors appropriately
-
-            HttpResponse::Ok()
-                .json(serde_json::json!({ "vote_token": token , "message": "Authentication successful"}))
+            (option.get("question_id"), ballot)
         }
-        Err(_) => HttpResponse::InternalServerError().json("Failed to finish authentication"),
-    }
-}
\ No newline at end of file
+        (None, None) => {
+            return Err(AppError::bad_request(
+                "missing_ballot",
+                "Send a ballot with its question_id, or an option_id.",
+            ))
+        }
+    };
+
+    let poll = sqlx::query(
+        r#"
+        SELECT status,
+               (opens_at IS NOT NULL AND opens_at > UTC_TIMESTAMP()) AS not_open_yet,
+               (closes_at IS NOT NULL AND closes_at <= UTC_TIMESTAMP()) AS past_close
+        FROM polls
+        WHERE id = ?
+        "#,
+    )
+    .bind(req_body.poll_id)
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::not_found("poll_not_found", "Poll not found."))?;
+    ensure_poll_open(&poll)?;
+
+    // Validate the ballot now so a bad one fails before the user casts it;
+    // the vote endpoint checks it again against the poll as it is then
+    let mut conn = pool.acquire().await?;
+    let (spec, option_ids) = load_question(&mut conn, req_body.poll_id, question_id).await?;
+    let ballot = spec.check_ballot(&ballot, &option_ids)?;
+
+    // Bind the token to the user, poll, question and ballot
+    let token =
+        issue_vote_token(&settings.jwt, email, req_body.poll_id, question_id, &ballot)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "vote_token": token,
+        "expires_in": settings.jwt.vote_token_ttl_secs,
+        "message": "Authentication successful"
+    })))
+}
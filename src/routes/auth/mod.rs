@@ -1,13 +1,25 @@
+pub mod credentials;
+pub mod discoverable_authentication;
 pub mod finish_authentication;
 pub mod finish_registration;
 pub mod register_start;
 pub mod start_authentication;
 pub mod finish_verification;
+pub mod recovery;
+pub mod refresh_token;
+pub mod sessions;
 pub mod start_verification;
+pub mod user_roles;
 
+pub use credentials::*;
+pub use discoverable_authentication::*;
 pub use finish_authentication::*;
 pub use finish_registration::*;
 pub use register_start::*;
 pub use start_authentication::*;
 pub use finish_verification::*;
+pub use recovery::*;
+pub use refresh_token::*;
+pub use sessions::*;
 pub use start_verification::*;
+pub use user_roles::*;
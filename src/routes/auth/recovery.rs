@@ -0,0 +1,125 @@
+use actix_web::{
+    http::header::{AUTHORIZATION, USER_AGENT},
+    post,
+    web::{self, Data},
+    HttpRequest, HttpResponse,
+};
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use webauthn_rs::Webauthn;
+
+use super::{begin_credential_enrollment, verify_credential_enrollment, FinishEnrollmentRequest};
+use crate::config::{decode_recovery_token, issue_recovery_token, AuthUser, RecoveryClaims, Settings};
+use crate::errors::AppError;
+use crate::{
+    complete_recovery, issue_recovery_codes, redeem_recovery_code, revoke_session,
+    store_user_credential,
+};
+
+#[derive(Deserialize)]
+struct RecoverRequest {
+    email: String,
+    recovery_code: String,
+}
+
+// The recovery token travels as a bearer token like an access token, but is
+// checked against its own audience here instead of by the JWT middleware
+fn recovery_claims(request: &HttpRequest, settings: &Settings) -> Result<RecoveryClaims, AppError> {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|auth_header| auth_header.to_str().ok())
+        .and_then(|auth_str| auth_str.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::unauthorized("missing_token", "No token provided."))?;
+    decode_recovery_token(&settings.jwt, token)
+}
+
+// Spend a recovery code. Every session of the account is signed out, since the
+// lost passkey may be in someone else's hands, and a recovery token is returned
+// that can only enroll a new passkey.
+#[post("/recover")]
+pub async fn recover_account(
+    pool: web::Data<MySqlPool>,
+    settings: Data<Settings>,
+    req_body: web::Json<RecoverRequest>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /recover");
+    let email = &req_body.email;
+    let device = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok());
+    let ip_address = request
+        .connection_info()
+        .realip_remote_addr()
+        .map(str::to_owned);
+
+    let recovery_id = redeem_recovery_code(
+        email,
+        &req_body.recovery_code,
+        device,
+        ip_address.as_deref(),
+        &pool,
+    )
+    .await?;
+    println!("Recovery code used for email: {}", email);
+
+    revoke_session(email, None, &pool).await?;
+    let token = issue_recovery_token(&settings.jwt, email, &recovery_id)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "recovery_token": token,
+        "expires_in": settings.jwt.recovery_token_ttl_secs,
+    })))
+}
+
+#[post("/recover/register/start")]
+pub async fn start_recovery_enrollment(
+    pool: web::Data<MySqlPool>,
+    webauthn: Data<Webauthn>,
+    settings: Data<Settings>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /recover/register/start");
+    let claims = recovery_claims(&request, &settings)?;
+    let challenge = begin_credential_enrollment(&claims.sub, &webauthn, &settings, &pool).await?;
+    Ok(HttpResponse::Ok().json(challenge))
+}
+
+// Each recovery token enrolls exactly one passkey; afterwards the user signs in normally
+#[post("/recover/register/finish")]
+pub async fn finish_recovery_enrollment(
+    pool: web::Data<MySqlPool>,
+    webauthn: Data<Webauthn>,
+    settings: Data<Settings>,
+    req_body: web::Json<FinishEnrollmentRequest>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /recover/register/finish");
+    let claims = recovery_claims(&request, &settings)?;
+
+    let (passkey, nickname) =
+        verify_credential_enrollment(&claims.sub, &req_body, &webauthn, &pool).await?;
+
+    // The recovery is only spent once the new passkey is stored
+    let mut tx = pool.begin().await?;
+    complete_recovery(&claims.jti, &claims.sub, &mut tx).await?;
+    store_user_credential(&claims.sub, &passkey, nickname, &mut tx).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().json("Passkey added. Sign in with it to continue."))
+}
+
+// Replace the caller's recovery codes, e.g. after using one or losing the list
+#[post("/recovery-codes")]
+pub async fn regenerate_recovery_codes(
+    pool: web::Data<MySqlPool>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /recovery-codes");
+    let mut tx = pool.begin().await?;
+    let codes = issue_recovery_codes(&user.email, &mut tx).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "recovery_codes": codes })))
+}
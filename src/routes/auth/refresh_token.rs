@@ -0,0 +1,43 @@
+use actix_web::{
+    post,
+    web::{self, Data},
+    HttpResponse,
+};
+use serde::Deserialize;
+use sqlx::MySqlPool;
+
+use crate::{
+    config::{issue_access_token, Settings},
+    get_user_roles, rotate_refresh_token,
+};
+use crate::errors::AppError;
+
+#[derive(Deserialize)]
+struct RefreshTokenRequest {
+    refresh_token: String,
+}
+
+// Trade a refresh token for a new access token and a rotated refresh token
+#[post("/token/refresh")]
+pub async fn refresh_token(
+    pool: web::Data<MySqlPool>,
+    settings: Data<Settings>,
+    req_body: web::Json<RefreshTokenRequest>,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /token/refresh");
+
+    let rotated = rotate_refresh_token(
+        &req_body.refresh_token,
+        settings.jwt.refresh_token_ttl_secs,
+        &pool,
+    )
+    .await?;
+    let roles = get_user_roles(&rotated.email, &pool).await?;
+    let token = issue_access_token(&settings.jwt, &rotated.email, roles, &rotated.family_id)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "token": token,
+        "refresh_token": rotated.refresh_token,
+        "expires_in": settings.jwt.access_token_ttl_secs,
+    })))
+}
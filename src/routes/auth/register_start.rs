@@ -1,22 +1,17 @@
 use actix_web::{
     post,
     web::{self, Data},
-    HttpResponse, Responder,
+    HttpResponse,
 };
-use jsonwebtoken::{encode, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
+use sqlx::{MySqlPool, Row};
 use uuid::Uuid;
-use webauthn_rs::prelude::{
-    Base64UrlSafeData, CreationChallengeResponse, CredentialID, Passkey, PublicKeyCredential,
-    RegisterPublicKeyCredential,
-};
+use webauthn_rs::Webauthn;
+use webauthn_rs::prelude::Base64UrlSafeData;
 
-use crate::{
-    config::create_webauthn_instance, get_passkey_auth_state, get_passkey_registration,
-    get_user_credentials, get_user_credentials_passkeys, store_passkey_auth_state,
-    store_passkey_registration, store_user_credential, update_credential_counter,
-};
+use crate::{get_user_credentials, get_user_handle, store_passkey_registration, CeremonyChallenge};
+use crate::config::{require_resident_key, Settings};
+use crate::errors::AppError;
 
 #[derive(Deserialize)]
 struct StartRegistrationRequest {
@@ -33,35 +28,56 @@ struct RegisterStartResponse {
 #[post("/register/start")]
 async fn register_start(
     pool: Data<MySqlPool>,
+    webauthn: Data<Webauthn>, // Shared relying party built at startup
+    settings: Data<Settings>,
     body: web::Json<StartRegistrationRequest>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     println!("/POST register/start");
-    let data = create_webauthn_instance();
-    let user_unique_id = Uuid::new_v4(); // Generate a new UUID for the user
+    let data = webauthn.get_ref();
     let email = &body.email;
     let display_name = &body.display_name;
 
-    // Check if the user already exists with the given email
-    let user_exists = sqlx::query!("SELECT COUNT(*) as count FROM users WHERE email = ?", email)
-        .fetch_one(&**pool)
-        .await
-        .map(|record| record.count > 0)
-        .unwrap_or(false);
+    // An account with a passkey is taken; one without is an abandoned registration
+    // that can be restarted with the same user handle
+    let existing = sqlx::query(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM user_credentials uc WHERE uc.user_id = u.id) AS has_passkey
+        FROM users u
+        WHERE u.email = ?
+        "#,
+    )
+    .bind(email)
+    .fetch_optional(pool.get_ref())
+    .await?;
 
-    if user_exists {
-        return HttpResponse::BadRequest().json("User already exists");
-    }
+    let user_unique_id = match existing {
+        Some(row) if row.get::<bool, _>("has_passkey") => {
+            return Err(AppError::conflict("user_exists", "User already exists."));
+        }
+        Some(_) => get_user_handle(email, &pool).await?,
+        None => Uuid::new_v4(), // Generate a new UUID for the user
+    };
     // Get the user's passkeys from the database
     let exclude_credentials = get_user_credentials(email, &pool).await;
 
-    match data.start_passkey_registration(user_unique_id, email, display_name, exclude_credentials)
-    {
-        Ok((challenge_response, passkey_registration)) => {
-            store_passkey_registration(email, display_name, &passkey_registration, &pool).await;
+    let (mut challenge_response, passkey_registration) = data
+        .start_passkey_registration(user_unique_id, email, display_name, exclude_credentials)
+        .map_err(|err| AppError::internal(format!("Failed to start registration: {}", err)))?;
+    require_resident_key(&mut challenge_response);
 
-            // Send the challenge to the client
-            HttpResponse::Ok().json(challenge_response)
-        }
-        Err(_) => HttpResponse::InternalServerError().json("Failed to start registration"),
-    }
+    let ceremony_id = store_passkey_registration(
+        email,
+        display_name,
+        user_unique_id,
+        &passkey_registration,
+        settings.webauthn.timeout_secs,
+        &pool,
+    )
+    .await?;
+
+    // Send the challenge to the client along with the id to finish it with
+    Ok(HttpResponse::Ok().json(CeremonyChallenge {
+        ceremony_id,
+        challenge: challenge_response,
+    }))
 }
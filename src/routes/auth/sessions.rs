@@ -0,0 +1,83 @@
+use actix_web::{
+    delete, get, post,
+    web::{self, Data},
+    HttpResponse,
+};
+use sqlx::MySqlPool;
+
+use crate::config::AuthUser;
+use crate::errors::AppError;
+use crate::{get_user_sessions, revoke_session};
+
+// List the caller's active sessions, flagging the one making the request
+#[get("/sessions")]
+pub async fn list_sessions(
+    pool: web::Data<MySqlPool>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("GET /sessions");
+    let sessions = get_user_sessions(&user.email, &pool).await?;
+
+    let sessions: Vec<serde_json::Value> = sessions
+        .into_iter()
+        .map(|session| {
+            let current = user.session_id.as_deref() == Some(session.id.as_str());
+            serde_json::json!({
+                "id": session.id,
+                "device": session.device,
+                "ip_address": session.ip_address,
+                "created_at": session.created_at,
+                "last_seen_at": session.last_seen_at,
+                "current": current,
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+// Revoke one of the caller's sessions, e.g. the one on a lost laptop
+#[delete("/sessions/{session_id}")]
+pub async fn delete_session(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<String>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let session_id = path.into_inner();
+    println!("DELETE /sessions/{}", session_id);
+
+    if revoke_session(&user.email, Some(&session_id), &pool).await? == 0 {
+        return Err(AppError::not_found("session_not_found", "Session not found."));
+    }
+
+    Ok(HttpResponse::Ok().json("Session revoked."))
+}
+
+// Revoke every session of the caller, including the current one
+#[delete("/sessions")]
+pub async fn delete_all_sessions(
+    pool: web::Data<MySqlPool>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("DELETE /sessions");
+    let revoked = revoke_session(&user.email, None, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "All sessions revoked.",
+        "revoked": revoked,
+    })))
+}
+
+// End the session the request was made with
+#[post("/logout")]
+pub async fn logout(
+    pool: web::Data<MySqlPool>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /logout");
+    if let Some(session_id) = user.session_id.as_deref() {
+        revoke_session(&user.email, Some(session_id), &pool).await?;
+    }
+
+    Ok(HttpResponse::Ok().json("Logged out."))
+}
@@ -1,22 +1,18 @@
 use actix_web::{
     post,
     web::{self, Data},
-    HttpResponse, Responder,
+    HttpResponse,
 };
-use jsonwebtoken::{encode, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
 use sqlx::MySqlPool;
-use uuid::Uuid;
-use webauthn_rs::prelude::{
-    Base64UrlSafeData, CreationChallengeResponse, CredentialID, Passkey, PublicKeyCredential,
-    RegisterPublicKeyCredential,
-};
+use webauthn_rs::Webauthn;
+use webauthn_rs::prelude::RequestChallengeResponse;
 
 use crate::{
-    config::create_webauthn_instance, get_passkey_auth_state, get_passkey_registration,
-    get_user_credentials, get_user_credentials_passkeys, store_passkey_auth_state,
-    store_passkey_registration, store_user_credential, update_credential_counter,
+    get_user_credentials_passkeys, store_passkey_auth_state, CeremonyChallenge, CeremonyKind,
 };
+use crate::config::Settings;
+use crate::errors::AppError;
 
 
 
@@ -26,31 +22,59 @@ pub struct StartAuthenticationRequest {
    pub email: String,
 }
 
+// Issue a passkey challenge for `email` and persist the ceremony state.
+// Shared by the login flow and vote verification, told apart by `kind`.
+pub async fn begin_passkey_authentication(
+    email: &str,
+    kind: CeremonyKind,
+    webauthn: &Webauthn,
+    settings: &Settings,
+    pool: &MySqlPool,
+) -> Result<CeremonyChallenge<RequestChallengeResponse>, AppError> {
+    // Retrieve the user's credentials from the database
+    let user_passkeys = get_user_credentials_passkeys(email, pool).await?;
+    if user_passkeys.is_empty() {
+        return Err(AppError::not_found("user_not_found", "User not found."));
+    }
+
+    // Start WebAuthn authentication
+    let (challenge_response, passkey_auth_state) = webauthn
+        .start_passkey_authentication(&user_passkeys)
+        .map_err(|err| AppError::internal(format!("Failed to start authentication: {}", err)))?;
+
+    // Persist the `passkey_auth_state` under a fresh ceremony id
+    let ceremony_id = store_passkey_auth_state(
+        email,
+        kind,
+        &passkey_auth_state,
+        settings.webauthn.timeout_secs,
+        pool,
+    )
+    .await?;
+
+    Ok(CeremonyChallenge {
+        ceremony_id,
+        challenge: challenge_response,
+    })
+}
+
 #[post("/login/start")]
 pub async fn start_authentication(
     pool: web::Data<sqlx::MySqlPool>, // Your MySQL connection pool
+    webauthn: Data<Webauthn>, // Shared relying party built at startup
+    settings: Data<Settings>,
     req_body: web::Json<StartAuthenticationRequest>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     println!("POST /login/start");
-    let data = create_webauthn_instance();
-    let email = &req_body.email;
-
-    // Retrieve the user's credentials from the database
-    let user_passkeys = get_user_credentials_passkeys(email, &pool).await;
-    let user_passkeys = match user_passkeys {
-        None => return HttpResponse::BadRequest().json("User not found"),
-        Some(user_passkeys) => user_passkeys,
-    };
+    let challenge_response = begin_passkey_authentication(
+        &req_body.email,
+        CeremonyKind::Authentication,
+        &webauthn,
+        &settings,
+        &pool,
+    )
+    .await?;
 
-    // Start WebAuthn authentication
-    match data.start_passkey_authentication(&user_passkeys) {
-        Ok((challenge_response, passkey_auth_state)) => {
-            // Persist the `passkey_auth_state` for this user
-            store_passkey_auth_state(email, &passkey_auth_state, &pool).await;
-
-            // Send the challenge to the client
-            HttpResponse::Ok().json(challenge_response)
-        }
-        Err(_) => HttpResponse::InternalServerError().json("Failed to start authentication"),
-    }
+    // Send the challenge to the client
+    Ok(HttpResponse::Ok().json(challenge_response))
 }
@@ -1,38 +1,44 @@
-use actix_web::{post, web, HttpResponse, Responder};
-use reqwest::Client;
+use actix_web::{
+    post,
+    web::{self, Data},
+    HttpResponse,
+};
 use sqlx::MySqlPool;
+use webauthn_rs::Webauthn;
 
-use super::StartAuthenticationRequest;
+use super::{begin_passkey_authentication, StartAuthenticationRequest};
+use crate::config::{AuthUser, Settings};
+use crate::CeremonyKind;
+use crate::errors::AppError;
 
 #[post("/start_verification")]
 pub async fn start_verification(
     pool: web::Data<MySqlPool>, // Your MySQL connection pool
+    webauthn: Data<Webauthn>, // Shared relying party built at startup
+    settings: Data<Settings>,
     req_body: web::Json<StartAuthenticationRequest>,
-) -> impl Responder {
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
     println!("POST /login/start_verification");
 
-    // Create a new HTTP client
-    let client = Client::new();
-    let auth_url = "http://0.0.0.0:3001/login/start".to_string(); // Set your base URL
-
-    // Convert req_body to JSON
-    let req_json = serde_json::to_value(&req_body.into_inner()).unwrap();
-
-    // Send a request to the start_authentication route
-    let response = client
-        .post(&auth_url)
-        .json(&req_json)
-        .send()
-        .await;
+    // A vote can only be verified with the caller's own passkey
+    if req_body.email != user.email {
+        return Err(AppError::forbidden(
+            "email_mismatch",
+            "Verification must use the signed-in account.",
+        ));
+    }
 
-    match response {
-        Ok(resp) => {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_else(|_| "Failed to read response body".to_string());
+    // Same ceremony as /login/start, issued in-process rather than over HTTP
+    // A separate ceremony kind keeps login challenges from being spent on votes
+    let challenge_response = begin_passkey_authentication(
+        &user.email,
+        CeremonyKind::Verification,
+        &webauthn,
+        &settings,
+        &pool,
+    )
+    .await?;
 
-            // Return the response status and body from start_authentication
-            HttpResponse::build(actix_web::http::StatusCode::from_u16(status.as_u16()).unwrap()).body(body)
-        }
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
-    }
-}
\ No newline at end of file
+    Ok(HttpResponse::Ok().json(challenge_response))
+}
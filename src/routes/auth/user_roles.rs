@@ -0,0 +1,108 @@
+use actix_web::{
+    delete, get, put,
+    web::{self},
+    HttpResponse,
+};
+use serde::Serialize;
+use sqlx::{MySqlPool, Row};
+
+use crate::config::AuthUser;
+use crate::errors::AppError;
+use crate::polls::{require_admin, ADMIN_ROLE};
+
+#[derive(Serialize)]
+struct UserRoleResponse {
+    email: String,
+    role: String,
+    granted_by: Option<String>,
+    created_at: Option<String>,
+}
+
+fn validate_site_role(role: &str) -> Result<(), AppError> {
+    if role != ADMIN_ROLE {
+        return Err(AppError::bad_request("invalid_role", "The only site role is admin."));
+    }
+    Ok(())
+}
+
+// Every site wide role grant; admins only
+#[get("/api/admin/roles")]
+pub async fn list_user_roles(
+    pool: web::Data<MySqlPool>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("GET /api/admin/roles");
+    require_admin(&user, &pool).await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT user_email, role, granted_by, CAST(created_at AS CHAR) AS created_at
+        FROM user_roles
+        ORDER BY role, user_email
+        "#,
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let roles: Vec<UserRoleResponse> = rows
+        .iter()
+        .map(|row| UserRoleResponse {
+            email: row.get("user_email"),
+            role: row.get("role"),
+            granted_by: row.get("granted_by"),
+            created_at: row.get("created_at"),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+// Tokens pick up the change on their next refresh; the guards read the database
+#[put("/api/admin/users/{email}/roles/{role}")]
+pub async fn grant_user_role(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<(String, String)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (email, role) = path.into_inner();
+    println!("PUT /api/admin/users/{}/roles/{}", email, role);
+    require_admin(&user, &pool).await?;
+    validate_site_role(&role)?;
+
+    sqlx::query(
+        r#"
+        INSERT IGNORE INTO user_roles (user_email, role, granted_by, created_at)
+        VALUES (?, ?, ?, UTC_TIMESTAMP())
+        "#,
+    )
+    .bind(&email)
+    .bind(&role)
+    .bind(&user.email)
+    .execute(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json("Role granted."))
+}
+
+#[delete("/api/admin/users/{email}/roles/{role}")]
+pub async fn revoke_user_role(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<(String, String)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (email, role) = path.into_inner();
+    println!("DELETE /api/admin/users/{}/roles/{}", email, role);
+    require_admin(&user, &pool).await?;
+    validate_site_role(&role)?;
+
+    let removed = sqlx::query("DELETE FROM user_roles WHERE user_email = ? AND role = ?")
+        .bind(&email)
+        .bind(&role)
+        .execute(pool.get_ref())
+        .await?;
+    if removed.rows_affected() == 0 {
+        return Err(AppError::not_found("role_not_found", "User doesn't have this role."));
+    }
+
+    Ok(HttpResponse::Ok().json("Role revoked."))
+}
@@ -1,5 +1,8 @@
 pub mod auth;
 pub use auth::*;
 
+pub mod organizations;
+pub use organizations::*;
+
 pub mod polling;
 pub use polling::*;
@@ -0,0 +1,238 @@
+use actix_web::{
+    delete, get, post,
+    web::{self},
+    HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool, Row};
+use uuid::Uuid;
+
+use crate::config::AuthUser;
+use crate::errors::{AppError, FieldError};
+use crate::hash_token;
+use crate::polls::{require_org_role, OrgRole};
+
+// Invitations are valid for a week
+const INVITATION_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+struct CreateInvitationRequest {
+    email: String,
+    role: Option<String>, // member (default) or admin
+}
+
+#[derive(Deserialize)]
+struct AcceptInvitationRequest {
+    token: String,
+}
+
+#[derive(Serialize)]
+struct InvitationResponse {
+    id: i64,
+    email: String,
+    role: String,
+    invited_by: String,
+    created_at: Option<String>,
+    expires_at: Option<String>,
+}
+
+// Invite someone by email. The returned token is delivered out of band, e.g.
+// in an invitation link, and is accepted by the invitee once signed in.
+#[post("/api/organizations/{organization_id}/invitations")]
+pub async fn create_invitation(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<i64>,
+    req: web::Json<CreateInvitationRequest>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let organization_id = path.into_inner();
+    println!("POST /api/organizations/{}/invitations", organization_id);
+    require_org_role(&user, organization_id, OrgRole::Admin, &pool).await?;
+
+    let email = req.email.trim();
+    if email.is_empty() || !email.contains('@') {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "email",
+            "A valid email address is required.",
+        )]));
+    }
+    let role = match req.role.as_deref() {
+        Some(role) => role.parse()?,
+        None => OrgRole::Member,
+    };
+    if role == OrgRole::Owner {
+        return Err(AppError::bad_request(
+            "invalid_role",
+            "Invitations can grant member or admin.",
+        ));
+    }
+
+    let already_member = sqlx::query(
+        "SELECT 1 FROM organization_members WHERE organization_id = ? AND user_email = ?",
+    )
+    .bind(organization_id)
+    .bind(email)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .is_some();
+    if already_member {
+        return Err(AppError::conflict("already_member", "This user is already a member."));
+    }
+
+    // Two v4 UUIDs give 244 random bits
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let invitation_id = sqlx::query(
+        r#"
+        INSERT INTO organization_invitations
+            (organization_id, email, role, token_hash, invited_by, created_at, expires_at)
+        VALUES (?, ?, ?, ?, ?, UTC_TIMESTAMP(), DATE_ADD(UTC_TIMESTAMP(), INTERVAL ? SECOND))
+        "#,
+    )
+    .bind(organization_id)
+    .bind(email)
+    .bind(role.as_str())
+    .bind(hash_token(&token))
+    .bind(&user.email)
+    .bind(INVITATION_TTL_SECS)
+    .execute(pool.as_ref())
+    .await?
+    .last_insert_id();
+
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "invitation_id": invitation_id,
+        "token": token,
+        "expires_in": INVITATION_TTL_SECS,
+    })))
+}
+
+// Pending invitations of an organization
+#[get("/api/organizations/{organization_id}/invitations")]
+pub async fn get_invitations(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<i64>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let organization_id = path.into_inner();
+    println!("GET /api/organizations/{}/invitations", organization_id);
+    require_org_role(&user, organization_id, OrgRole::Admin, &pool).await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT id, email, role, invited_by,
+               CAST(created_at AS CHAR) AS created_at,
+               CAST(expires_at AS CHAR) AS expires_at
+        FROM organization_invitations
+        WHERE organization_id = ? AND accepted_at IS NULL AND expires_at > UTC_TIMESTAMP()
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(organization_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let invitations: Vec<InvitationResponse> = rows
+        .iter()
+        .map(|row| InvitationResponse {
+            id: row.get("id"),
+            email: row.get("email"),
+            role: row.get("role"),
+            invited_by: row.get("invited_by"),
+            created_at: row.get("created_at"),
+            expires_at: row.get("expires_at"),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(invitations))
+}
+
+#[delete("/api/organizations/{organization_id}/invitations/{invitation_id}")]
+pub async fn revoke_invitation(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(i64, i64)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (organization_id, invitation_id) = path.into_inner();
+    println!(
+        "DELETE /api/organizations/{}/invitations/{}",
+        organization_id, invitation_id
+    );
+    require_org_role(&user, organization_id, OrgRole::Admin, &pool).await?;
+
+    let removed = sqlx::query(
+        r#"
+        DELETE FROM organization_invitations
+        WHERE id = ? AND organization_id = ? AND accepted_at IS NULL
+        "#,
+    )
+    .bind(invitation_id)
+    .bind(organization_id)
+    .execute(pool.as_ref())
+    .await?;
+    if removed.rows_affected() == 0 {
+        return Err(AppError::not_found("invitation_not_found", "Invitation not found."));
+    }
+
+    Ok(HttpResponse::Ok().json("Invitation revoked."))
+}
+
+// Join the organization of an invitation addressed to the caller's email
+#[post("/api/invitations/accept")]
+pub async fn accept_invitation(
+    pool: web::Data<Pool<MySql>>,
+    req: web::Json<AcceptInvitationRequest>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /api/invitations/accept");
+    let mut tx = pool.begin().await?;
+
+    let invitation = sqlx::query(
+        r#"
+        SELECT id, organization_id, email, role
+        FROM organization_invitations
+        WHERE token_hash = ? AND accepted_at IS NULL AND expires_at > UTC_TIMESTAMP()
+        FOR UPDATE
+        "#,
+    )
+    .bind(hash_token(&req.token))
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| {
+        AppError::not_found("invitation_not_found", "Invitation not found or expired.")
+    })?;
+
+    if !invitation
+        .get::<String, _>("email")
+        .eq_ignore_ascii_case(&user.email)
+    {
+        return Err(AppError::forbidden(
+            "invitation_email_mismatch",
+            "This invitation was sent to a different email address.",
+        ));
+    }
+
+    let organization_id: i64 = invitation.get("organization_id");
+    // Accepting again as an existing member keeps the current role
+    sqlx::query(
+        r#"
+        INSERT IGNORE INTO organization_members (organization_id, user_email, role, joined_at)
+        VALUES (?, ?, ?, UTC_TIMESTAMP())
+        "#,
+    )
+    .bind(organization_id)
+    .bind(&user.email)
+    .bind(invitation.get::<String, _>("role"))
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query("UPDATE organization_invitations SET accepted_at = UTC_TIMESTAMP() WHERE id = ?")
+        .bind(invitation.get::<i64, _>("id"))
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Invitation accepted",
+        "organization_id": organization_id,
+    })))
+}
@@ -0,0 +1,5 @@
+pub mod invitations;
+pub mod organizations;
+
+pub use invitations::*;
+pub use organizations::*;
@@ -0,0 +1,193 @@
+use actix_web::{
+    delete, get, post,
+    web::{self},
+    HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool, Row};
+
+use crate::config::AuthUser;
+use crate::errors::{AppError, FieldError};
+use crate::polls::{org_role, require_org_role, OrgRole};
+
+const MAX_ORGANIZATION_NAME_LEN: usize = 100;
+
+#[derive(Deserialize)]
+struct CreateOrganizationRequest {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct OrganizationResponse {
+    id: i64,
+    name: String,
+    role: String,
+    joined_at: Option<String>,
+}
+
+#[derive(Serialize)]
+struct MemberResponse {
+    email: String,
+    role: String,
+    joined_at: Option<String>,
+}
+
+// Create an organization; the caller becomes its owner
+#[post("/api/organizations")]
+pub async fn create_organization(
+    pool: web::Data<Pool<MySql>>,
+    req: web::Json<CreateOrganizationRequest>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("POST /api/organizations");
+    let name = req.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_ORGANIZATION_NAME_LEN {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "name",
+            format!("Name must be 1 to {} characters.", MAX_ORGANIZATION_NAME_LEN),
+        )]));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let organization_id = sqlx::query(
+        r#"
+        INSERT INTO organizations (name, created_by, created_at)
+        VALUES (?, ?, UTC_TIMESTAMP())
+        "#,
+    )
+    .bind(name)
+    .bind(&user.email)
+    .execute(&mut tx)
+    .await?
+    .last_insert_id();
+
+    sqlx::query(
+        r#"
+        INSERT INTO organization_members (organization_id, user_email, role, joined_at)
+        VALUES (?, ?, 'owner', UTC_TIMESTAMP())
+        "#,
+    )
+    .bind(organization_id)
+    .bind(&user.email)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "message": "Organization created successfully",
+        "organization_id": organization_id,
+    })))
+}
+
+// Organizations the caller belongs to
+#[get("/api/organizations")]
+pub async fn get_organizations(
+    pool: web::Data<Pool<MySql>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    println!("GET /api/organizations");
+    let rows = sqlx::query(
+        r#"
+        SELECT o.id, o.name, m.role, CAST(m.joined_at AS CHAR) AS joined_at
+        FROM organization_members m
+        INNER JOIN organizations o ON o.id = m.organization_id
+        WHERE m.user_email = ?
+        ORDER BY o.name
+        "#,
+    )
+    .bind(&user.email)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let organizations: Vec<OrganizationResponse> = rows
+        .iter()
+        .map(|row| OrganizationResponse {
+            id: row.get("id"),
+            name: row.get("name"),
+            role: row.get("role"),
+            joined_at: row.get("joined_at"),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(organizations))
+}
+
+#[get("/api/organizations/{organization_id}/members")]
+pub async fn get_organization_members(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<i64>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let organization_id = path.into_inner();
+    println!("GET /api/organizations/{}/members", organization_id);
+    require_org_role(&user, organization_id, OrgRole::Member, &pool).await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT user_email, role, CAST(joined_at AS CHAR) AS joined_at
+        FROM organization_members
+        WHERE organization_id = ?
+        ORDER BY FIELD(role, 'owner', 'admin', 'member'), user_email
+        "#,
+    )
+    .bind(organization_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let members: Vec<MemberResponse> = rows
+        .iter()
+        .map(|row| MemberResponse {
+            email: row.get("user_email"),
+            role: row.get("role"),
+            joined_at: row.get("joined_at"),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(members))
+}
+
+// Remove a member, or leave when the email is the caller's own. Members can only
+// be removed by someone with a higher role, and the owner can't be removed.
+#[delete("/api/organizations/{organization_id}/members/{email}")]
+pub async fn remove_organization_member(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(i64, String)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (organization_id, email) = path.into_inner();
+    println!("DELETE /api/organizations/{}/members/{}", organization_id, email);
+    let caller_role = org_role(&user, organization_id, &pool).await?;
+
+    let member_role: OrgRole = sqlx::query_scalar::<_, String>(
+        "SELECT role FROM organization_members WHERE organization_id = ? AND user_email = ?",
+    )
+    .bind(organization_id)
+    .bind(&email)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::not_found("member_not_found", "Not a member of this organization."))?
+    .parse()?;
+
+    if member_role == OrgRole::Owner {
+        return Err(AppError::conflict(
+            "cannot_remove_owner",
+            "The organization owner can't be removed.",
+        ));
+    }
+    if email != user.email && caller_role <= member_role {
+        return Err(AppError::forbidden(
+            "insufficient_role",
+            "You can't remove this member.",
+        ));
+    }
+
+    sqlx::query("DELETE FROM organization_members WHERE organization_id = ? AND user_email = ?")
+        .bind(organization_id)
+        .bind(&email)
+        .execute(pool.as_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json("Member removed."))
+}
@@ -2,82 +2,41 @@ use actix::Addr;
 use actix_web::{
     post,
     web::{self, Data},
-    HttpMessage, HttpRequest, HttpResponse, Responder,
+    HttpResponse,
 };
-use serde::Deserialize;
-use sqlx::{MySql, Pool, Row};
+use sqlx::{MySql, Pool};
 
+use crate::config::AuthUser;
+use crate::errors::AppError;
+use crate::polls::{require_poll_role, transition_poll, PollRole, PollStatus};
 use crate::{Lobby, NotifyPollId};
 
-#[derive(Deserialize)]
-struct ClosePollRequest {
-    email: String, // Creator's email to verify ownership
-}
-
 #[post("/api/polls/{poll_id}/close")]
 pub async fn close_poll(
     pool: web::Data<Pool<MySql>>,
     path: web::Path<(String)>,
-    req: web::Json<ClosePollRequest>,
     srv: Data<Addr<Lobby>>,
-    request: HttpRequest,
-) -> impl Responder {
-    let poll_id = path.into_inner();
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id: i64 = path
+        .into_inner()
+        .parse()
+        .map_err(|_| AppError::bad_request("invalid_poll_id", "Invalid poll id."))?;
     println!("/POST polls/{}/close", poll_id);
 
-    // Check if the poll exists and if the creator matches the given email
-    let poll = sqlx::query(
-        r#"
-        SELECT creator_email, closed
-        FROM polls
-        WHERE id = ? AND closed = FALSE
-        "#,
-    )
-    .bind(poll_id.clone())
-    .fetch_one(pool.as_ref())
-    .await;
+    // Owners, editors and site admins manage a poll
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
 
-    match poll {
-        Ok(p) => {
-            let mut poll_creator = p.get::<String, _>("creator_email");
-            let req_user = request.headers().get("user_id").unwrap().to_str().unwrap();
+    // Scheduled, open and paused polls can be closed; any other status is
+    // refused with invalid_transition
+    let mut tx = pool.begin().await?;
+    transition_poll(&mut tx, poll_id, PollStatus::Closed, Some(user.email.as_str()), None)
+        .await?;
+    tx.commit().await?;
 
-            if poll_creator != req_user {
-                return HttpResponse::Unauthorized()
-                    .json("You are not authorized to close this poll.");
-            }
-            // Check if the requester is the creator
-            if poll_creator == req.email {
-                // Update the poll to close it
-                let update_result = sqlx::query(
-                    r#"
-                    UPDATE polls
-                    SET closed = TRUE
-                    WHERE id = ?
-                    "#,
-                )
-                .bind(poll_id.clone())
-                .execute(pool.as_ref())
-                .await;
-
-                match update_result {
-                    Ok(_) => {
-                        srv.send(NotifyPollId {
-                            poll_id: poll_id.parse::<i64>().unwrap(),
-                        })
-                        .await
-                        .map_err(|e| {
-                            eprintln!("Error sending message to lobby: {:?}", e);
-                            actix_web::error::ErrorInternalServerError(e)
-                        });
-                        HttpResponse::Ok().json("Poll closed successfully.")
-                    }
-                    Err(_) => HttpResponse::InternalServerError().json("Failed to close the poll."),
-                }
-            } else {
-                HttpResponse::Unauthorized().json("You are not authorized to close this poll.")
-            }
-        }
-        Err(_) => HttpResponse::NotFound().json("Poll not found or already closed."),
+    if let Err(e) = srv.send(NotifyPollId { poll_id }).await {
+        eprintln!("Error sending message to lobby: {:?}", e);
     }
+
+    Ok(HttpResponse::Ok().json("Poll closed successfully."))
 }
@@ -1,122 +1,318 @@
-use actix_web::{
-    dev::ServiceRequest, get, web, App, Error, HttpMessage, HttpRequest, HttpResponse, HttpServer,
-    Responder,
+use actix_web::{post, web, HttpResponse};
+use serde::Deserialize;
+use sqlx::{MySql, Pool};
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use super::poll_access::generate_share_token;
+use crate::config::AuthUser;
+use crate::errors::{AppError, FieldError};
+use crate::hash_token;
+use crate::polls::{
+    record_status_change, require_org_role, OrgRole, PollStatus, QuestionSpec, Visibility,
 };
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
-use serde::{Deserialize, Serialize};
 
-use actix_web::post;
-use sqlx::{mysql, query, MySql, Pool, Row};
+// Input limits. Titles fill polls.title (VARCHAR(255)); options are kept well under
+// poll_options.option_text (VARCHAR(1024)) so they stay readable as buttons.
+pub(crate) const MAX_TITLE_LEN: usize = 255;
+pub(crate) const MAX_DESCRIPTION_LEN: usize = 2000;
+pub(crate) const MAX_QUESTION_LEN: usize = 1000;
+pub(crate) const MAX_OPTION_LEN: usize = 255;
+pub(crate) const MAX_QUESTIONS: usize = 50;
+pub(crate) const MAX_OPTIONS: usize = 20;
 
 #[derive(Deserialize)]
 struct PollRequest {
     title: String,
     description: Option<String>,
-    creator_email: String,
+    organization_id: Option<i64>, // Only members of the organization will see the poll
+    visibility: Option<Visibility>, // Defaults to public
+    #[serde(default)]
+    allow_vote_changes: bool, // Let voters replace or withdraw their ballot while the poll is open
+    opens_at: Option<String>,  // RFC 3339; votes are refused before it
+    closes_at: Option<String>, // RFC 3339; the poll closes itself then
+    #[serde(default)]
+    draft: bool, // Keep the poll hidden and closed to votes until it is opened
     questions: Vec<QuestionRequest>,
 }
 
 #[derive(Deserialize)]
 struct QuestionRequest {
     question_text: String,
-    options: Vec<String>, // List of option texts
+    #[serde(default)]
+    options: Vec<String>, // List of option texts, empty for rating and free text
+    #[serde(flatten)]
+    spec: QuestionSpec, // Type and its limits, single choice by default
+}
+
+// Parse an RFC 3339 time into the UTC value stored in the database
+pub(crate) fn parse_schedule_time(field: &str, value: &str) -> Result<NaiveDateTime, FieldError> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .map(|time| time.with_timezone(&Utc).naive_utc())
+        .map_err(|_| {
+            FieldError::new(field, "Must be an RFC 3339 time, e.g. 2025-01-31T18:00:00Z.")
+        })
+}
+
+// Check a voting window: it must close after it opens, and in the future
+pub(crate) fn validate_schedule(
+    opens_at: Option<NaiveDateTime>,
+    closes_at: Option<NaiveDateTime>,
+) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if let Some(closes_at) = closes_at {
+        if closes_at <= Utc::now().naive_utc() {
+            errors.push(FieldError::new("closes_at", "Closing time must be in the future."));
+        }
+        if matches!(opens_at, Some(opens_at) if opens_at >= closes_at) {
+            errors.push(FieldError::new("closes_at", "Closing time must be after the opening time."));
+        }
+    }
+    errors
+}
+
+impl PollRequest {
+    // The voting window, or every problem with it
+    fn schedule(&self) -> Result<(Option<NaiveDateTime>, Option<NaiveDateTime>), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let mut parse = |field: &str, value: &Option<String>| match value {
+            Some(value) => parse_schedule_time(field, value)
+                .map_err(|err| errors.push(err))
+                .ok(),
+            None => None,
+        };
+        let opens_at = parse("opens_at", &self.opens_at);
+        let closes_at = parse("closes_at", &self.closes_at);
+        errors.extend(validate_schedule(opens_at, closes_at));
+        if errors.is_empty() {
+            Ok((opens_at, closes_at))
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Collect every problem with the request instead of stopping at the first one
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        let title = self.title.trim();
+        if title.is_empty() {
+            errors.push(FieldError::new("title", "Title must not be empty."));
+        } else if title.chars().count() > MAX_TITLE_LEN {
+            errors.push(FieldError::new(
+                "title",
+                format!("Title must be at most {} characters.", MAX_TITLE_LEN),
+            ));
+        }
+
+        if let Some(description) = &self.description {
+            if description.chars().count() > MAX_DESCRIPTION_LEN {
+                errors.push(FieldError::new(
+                    "description",
+                    format!("Description must be at most {} characters.", MAX_DESCRIPTION_LEN),
+                ));
+            }
+        }
+
+        if self.questions.is_empty() {
+            errors.push(FieldError::new("questions", "At least one question is required."));
+        } else if self.questions.len() > MAX_QUESTIONS {
+            errors.push(FieldError::new(
+                "questions",
+                format!("A poll can have at most {} questions.", MAX_QUESTIONS),
+            ));
+        }
+
+        for (i, question) in self.questions.iter().enumerate() {
+            let text = question.question_text.trim();
+            if text.is_empty() {
+                errors.push(FieldError::new(
+                    format!("questions[{}].question_text", i),
+                    "Question text must not be empty.",
+                ));
+            } else if text.chars().count() > MAX_QUESTION_LEN {
+                errors.push(FieldError::new(
+                    format!("questions[{}].question_text", i),
+                    format!("Question text must be at most {} characters.", MAX_QUESTION_LEN),
+                ));
+            }
+
+            errors.extend(
+                question
+                    .spec
+                    .validate(&format!("questions[{}]", i), question.options.len()),
+            );
+            if !question.spec.question_type.has_options() {
+                continue;
+            }
+
+            let mut seen = HashSet::new();
+            for (j, option) in question.options.iter().enumerate() {
+                let option = option.trim();
+                let field = format!("questions[{}].options[{}]", i, j);
+                if option.is_empty() {
+                    errors.push(FieldError::new(field, "Option must not be empty."));
+                } else if option.chars().count() > MAX_OPTION_LEN {
+                    errors.push(FieldError::new(
+                        field,
+                        format!("Option must be at most {} characters.", MAX_OPTION_LEN),
+                    ));
+                } else if !seen.insert(option.to_lowercase()) {
+                    errors.push(FieldError::new(field, "Duplicate option."));
+                }
+            }
+
+            if seen.len() < 2 {
+                errors.push(FieldError::new(
+                    format!("questions[{}].options", i),
+                    "At least two distinct options are required.",
+                ));
+            } else if question.options.len() > MAX_OPTIONS {
+                errors.push(FieldError::new(
+                    format!("questions[{}].options", i),
+                    format!("A question can have at most {} options.", MAX_OPTIONS),
+                ));
+            }
+        }
+
+        errors
+    }
 }
 
 #[post("/api/polls")]
 pub async fn create_poll(
     pool: web::Data<Pool<MySql>>,
-    mut poll_request: web::Json<PollRequest>,
-    req: HttpRequest,
-) -> impl Responder {
+    poll_request: web::Json<PollRequest>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
     println!("?POST /api/polls");
-    let user_email = req.headers().get("user_id").unwrap().to_str().unwrap();
-    println!("creator_email: {}", user_email);
-    poll_request.creator_email = user_email.to_string();
+
+    let mut errors = poll_request.validate();
+    let (opens_at, closes_at) = match poll_request.schedule() {
+        Ok(schedule) => schedule,
+        Err(schedule_errors) => {
+            errors.extend(schedule_errors);
+            (None, None)
+        }
+    };
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    // Any member may publish a poll into their organization
+    if let Some(organization_id) = poll_request.organization_id {
+        require_org_role(&user, organization_id, OrgRole::Member, &pool).await?;
+    }
+
+    // Unlisted polls are reached through a secret link; only its hash is stored
+    let visibility = poll_request.visibility.unwrap_or(Visibility::Public);
+    let share_token = (visibility == Visibility::Unlisted).then(generate_share_token);
+
+    // A poll that opens later waits for the scheduler
+    let status = if poll_request.draft {
+        PollStatus::Draft
+    } else if matches!(opens_at, Some(opens_at) if opens_at > Utc::now().naive_utc()) {
+        PollStatus::Scheduled
+    } else {
+        PollStatus::Open
+    };
+
+    // The poll, its questions and options are written together or not at all;
+    // returning early with `?` drops the transaction and rolls everything back
+    let mut tx = pool.begin().await?;
+
     let poll_id = sqlx::query(
         r#"
-        INSERT INTO polls (title, description, creator_email)
-        VALUES (?, ?, ?)
+        INSERT INTO polls
+            (title, description, creator_email, organization_id, visibility, share_token_hash,
+             allow_vote_changes, opens_at, closes_at, status)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         "#,
     )
-    .bind(&poll_request.title)
+    .bind(poll_request.title.trim())
     .bind(&poll_request.description)
-    .bind(&poll_request.creator_email)
-    .execute(pool.get_ref())
-    .await
-    .unwrap()
+    .bind(&user.email)
+    .bind(poll_request.organization_id)
+    .bind(visibility.as_str())
+    .bind(share_token.as_deref().map(hash_token))
+    .bind(poll_request.allow_vote_changes)
+    .bind(opens_at)
+    .bind(closes_at)
+    .bind(status.as_str())
+    .execute(&mut tx)
+    .await?
     .last_insert_id();
+    let poll_id = poll_id as i64;
+    record_status_change(
+        &mut tx,
+        poll_id,
+        None,
+        status,
+        Some(user.email.as_str()),
+        None,
+    )
+    .await?;
+
+    // The creator owns the poll and can share management through its roles
+    sqlx::query(
+        r#"
+        INSERT INTO poll_roles (poll_id, user_email, role, granted_by, created_at)
+        VALUES (?, ?, 'owner', ?, UTC_TIMESTAMP())
+        "#,
+    )
+    .bind(poll_id)
+    .bind(&user.email)
+    .bind(&user.email)
+    .execute(&mut tx)
+    .await?;
 
     // Insert questions and options
-    for question in &poll_request.questions {
-        let question_id: u64 = sqlx::query!(
+    for (position, question) in poll_request.questions.iter().enumerate() {
+        let spec = question.spec.clone().normalized();
+        let question_id = sqlx::query(
             r#"
-            INSERT INTO questions (poll_id, question_text)
-            VALUES (?, ?)
+            INSERT INTO questions
+                (poll_id, question_text, position, question_type,
+                 min_selections, max_selections, scale_min, scale_max)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
             "#,
-            poll_id,
-            question.question_text
         )
-        .execute(pool.get_ref())
-        .await
-        .unwrap()
+        .bind(poll_id)
+        .bind(question.question_text.trim())
+        .bind(position as i32)
+        .bind(spec.question_type.as_str())
+        .bind(spec.min_selections)
+        .bind(spec.max_selections)
+        .bind(spec.scale_min)
+        .bind(spec.scale_max)
+        .execute(&mut tx)
+        .await?
         .last_insert_id();
 
-        for option in &question.options {
-            sqlx::query!(
+        for (position, option) in question.options.iter().enumerate() {
+            sqlx::query(
                 r#"
-                INSERT INTO poll_options (question_id, option_text)
-                VALUES (?, ?)
+                INSERT INTO poll_options (question_id, option_text, position)
+                VALUES (?, ?, ?)
                 "#,
-                question_id,
-                option
             )
-            .execute(pool.get_ref())
-            .await
-            .unwrap();
+            .bind(question_id)
+            .bind(option.trim())
+            .bind(position as i32)
+            .execute(&mut tx)
+            .await?;
         }
     }
 
-    HttpResponse::Created().json(serde_json::json!({
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().json(serde_json::json!({
         "message": "Poll created successfully",
         "poll_id": poll_id,
-    }))
+        "visibility": visibility,
+        "status": status,
+        "share_token": share_token,
+    })))
 }
-
-// Define a struct for your claims
-#[derive(Debug, Deserialize)]
-struct Claims {
-    sub: String, // Email is stored in 'sub'
-    exp: usize,  // Expiration time
-}
-
-// #[get("/api/protected")]
-// pub async fn protected_handler(req: HttpRequest) -> Result<impl Responder, Error> {
-//     // Get the Authorization header
-//     let auth_header = req.headers().get("Authorization");
-
-//     if let Some(header_value) = auth_header {
-//         // Extract the token from the header
-//         if let Ok(token) = header_value.to_str() {
-//             // Remove the "Bearer " prefix
-//             let token = token.trim_start_matches("Bearer ");
-
-//             // Decode the token
-//             let secret_key = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-//             let decoding_key = DecodingKey::from_secret(secret_key.as_ref());
-//             let validation = Validation::new(Algorithm::HS256); // Use the same algorithm
-
-//             match decode::<Claims>(token, &decoding_key, &validation) {
-//                 Ok(token_data) => {
-//                     let email = token_data.claims.sub; // Extract email from the 'sub' field
-//                                                        // Return the user email in the response
-//                     return Ok(HttpResponse::Ok().json(format!("Hello, {}!", email)));
-//                 }
-//                 Err(_) => {
-//                     return Ok(HttpResponse::Unauthorized().json("Invalid token"));
-//                 }
-//             }
-//         }
-//     }
-
-//     Ok(HttpResponse::Unauthorized().json("Authorization header missing or invalid"))
-// }
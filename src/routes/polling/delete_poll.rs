@@ -0,0 +1,98 @@
+use actix::Addr;
+use actix_web::{
+    delete, post,
+    web::{self, Data},
+    HttpResponse,
+};
+use sqlx::{MySql, Pool};
+
+use super::parse_poll_id;
+use crate::config::AuthUser;
+use crate::errors::AppError;
+use crate::polls::{lock_poll_status, require_poll_role, transition_poll, PollRole, PollStatus};
+use crate::{Lobby, NotifyPollId, PollDeleted};
+
+// Soft delete: the poll is closed and hidden from listings, but its results
+// stay readable by whoever could see it and the owner can restore it
+#[post("/api/polls/{poll_id}/archive")]
+pub async fn archive_poll(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("POST /api/polls/{}/archive", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Owner, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+    transition_poll(&mut tx, poll_id, PollStatus::Archived, Some(user.email.as_str()), None).await?;
+    tx.commit().await?;
+
+    if let Err(e) = srv.send(NotifyPollId { poll_id }).await {
+        eprintln!("Error sending message to lobby: {:?}", e);
+    }
+
+    Ok(HttpResponse::Ok().json("Poll archived."))
+}
+
+// Bring an archived poll back into listings. It stays closed.
+#[post("/api/polls/{poll_id}/restore")]
+pub async fn restore_poll(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("POST /api/polls/{}/restore", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Owner, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+    // Closing is a valid move for open polls too; restore only undoes an archive
+    if lock_poll_status(&mut tx, poll_id).await? != PollStatus::Archived {
+        return Err(AppError::conflict("poll_not_archived", "Poll is not archived."));
+    }
+    transition_poll(&mut tx, poll_id, PollStatus::Closed, Some(user.email.as_str()), None).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json("Poll restored."))
+}
+
+// Hard delete. Questions, options, votes, roles and invites go with the poll
+// through their ON DELETE CASCADE foreign keys.
+#[delete("/api/polls/{poll_id}")]
+pub async fn delete_poll(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("DELETE /api/polls/{}", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Owner, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+
+    // Spent vote tokens reference the poll without a foreign key
+    sqlx::query("DELETE FROM used_vote_tokens WHERE poll_id = ?")
+        .bind(poll_id)
+        .execute(&mut tx)
+        .await?;
+
+    let deleted = sqlx::query("DELETE FROM polls WHERE id = ?")
+        .bind(poll_id)
+        .execute(&mut tx)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::not_found("poll_not_found", "Poll not found."));
+    }
+
+    tx.commit().await?;
+
+    // Drop the lobby group and disconnect everyone still watching
+    if let Err(e) = srv.send(PollDeleted { poll_id }).await {
+        eprintln!("Error sending message to lobby: {:?}", e);
+    }
+
+    Ok(HttpResponse::Ok().json("Poll deleted."))
+}
@@ -0,0 +1,681 @@
+use std::collections::HashSet;
+
+use actix::Addr;
+use actix_web::{
+    delete, patch, post, put,
+    web::{self, Data},
+    HttpResponse,
+};
+use chrono::{NaiveDateTime, Utc};
+use serde::Deserialize;
+use sqlx::{MySql, Pool, Row, Transaction};
+
+use super::create_poll::{
+    parse_schedule_time, validate_schedule, MAX_DESCRIPTION_LEN, MAX_OPTIONS, MAX_OPTION_LEN,
+    MAX_QUESTIONS, MAX_QUESTION_LEN, MAX_TITLE_LEN,
+};
+use super::parse_poll_id;
+use crate::config::AuthUser;
+use crate::errors::{AppError, FieldError};
+use crate::polls::{
+    lock_poll_status, require_poll_role, transition_poll, PollRole, PollStatus, QuestionSpec,
+};
+use crate::{Lobby, NotifyPollStructure};
+
+#[derive(Deserialize)]
+struct UpdatePollRequest {
+    title: Option<String>,
+    description: Option<String>, // An empty string clears it
+    allow_vote_changes: Option<bool>,
+    opens_at: Option<String>, // RFC 3339; an empty string clears it
+    closes_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AddQuestionRequest {
+    question_text: String,
+    #[serde(default)]
+    options: Vec<String>,
+    #[serde(flatten)]
+    spec: QuestionSpec,
+}
+
+#[derive(Deserialize)]
+struct UpdateQuestionRequest {
+    question_text: String,
+}
+
+#[derive(Deserialize)]
+struct OptionRequest {
+    option_text: String,
+}
+
+#[derive(Deserialize)]
+struct ReorderRequest {
+    ids: Vec<i64>, // Every question (or option) id, in the new order
+}
+
+fn validate_text(field: &str, label: &str, value: &str, max_len: usize) -> Result<(), AppError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(AppError::Validation(vec![FieldError::new(
+            field,
+            format!("{} must not be empty.", label),
+        )]));
+    }
+    if value.chars().count() > max_len {
+        return Err(AppError::Validation(vec![FieldError::new(
+            field,
+            format!("{} must be at most {} characters.", label, max_len),
+        )]));
+    }
+    Ok(())
+}
+
+// A schedule time from a PATCH body: None when absent, Some(None) when cleared
+fn parse_optional_time(
+    field: &str,
+    value: &Option<String>,
+) -> Result<Option<Option<NaiveDateTime>>, AppError> {
+    match value.as_deref().map(str::trim) {
+        None => Ok(None),
+        Some("") => Ok(Some(None)),
+        Some(value) => parse_schedule_time(field, value)
+            .map(|time| Some(Some(time)))
+            .map_err(|err| AppError::Validation(vec![err])),
+    }
+}
+
+// Lock the poll for the rest of the transaction. Votes take a shared lock on
+// the same row, so an edit and a vote never interleave. Closed and archived
+// polls are frozen. Returns the status it locked.
+async fn lock_open_poll(
+    tx: &mut Transaction<'_, MySql>,
+    poll_id: i64,
+) -> Result<PollStatus, AppError> {
+    let status = lock_poll_status(tx, poll_id).await?;
+    if status.is_closed() {
+        return Err(AppError::conflict("poll_closed", "Closed polls can't be edited."));
+    }
+    Ok(status)
+}
+
+// The question's type and limits, or 404 unless `question_id` belongs to `poll_id`
+async fn ensure_question_in_poll(
+    tx: &mut Transaction<'_, MySql>,
+    poll_id: i64,
+    question_id: i64,
+) -> Result<QuestionSpec, AppError> {
+    let question = sqlx::query(
+        r#"
+        SELECT question_type, min_selections, max_selections, scale_min, scale_max
+        FROM questions
+        WHERE id = ? AND poll_id = ?
+        "#,
+    )
+    .bind(question_id)
+    .bind(poll_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::not_found("question_not_found", "Question not found."))?;
+    QuestionSpec::from_row(&question)
+}
+
+// Votes already cast for `option_id`, or 404 if it isn't an option of `question_id`
+async fn option_votes(
+    tx: &mut Transaction<'_, MySql>,
+    question_id: i64,
+    option_id: i64,
+) -> Result<i64, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT (SELECT COUNT(*) FROM vote_selections WHERE option_id = po.id) AS votes
+        FROM poll_options po
+        WHERE po.id = ? AND po.question_id = ?
+        "#,
+    )
+    .bind(option_id)
+    .bind(question_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::not_found("option_not_found", "Option not found."))?;
+    Ok(row.get("votes"))
+}
+
+async fn touch_poll(tx: &mut Transaction<'_, MySql>, poll_id: i64) -> Result<(), AppError> {
+    sqlx::query("UPDATE polls SET updated_at = UTC_TIMESTAMP() WHERE id = ?")
+        .bind(poll_id)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+// Tell connected clients to refetch the poll
+async fn notify_structure(srv: &Addr<Lobby>, poll_id: i64) {
+    if let Err(e) = srv.send(NotifyPollStructure { poll_id }).await {
+        eprintln!("Error sending message to lobby: {:?}", e);
+    }
+}
+
+// `ids` must name every row of `existing` exactly once
+fn check_permutation(ids: &[i64], existing: &[i64]) -> Result<(), AppError> {
+    let wanted: HashSet<i64> = ids.iter().copied().collect();
+    let existing: HashSet<i64> = existing.iter().copied().collect();
+    if wanted.len() != ids.len() || wanted != existing {
+        return Err(AppError::bad_request(
+            "invalid_order",
+            "The order must list every id exactly once.",
+        ));
+    }
+    Ok(())
+}
+
+// Title, description, whether votes can be changed and the voting window may
+// be edited while the poll is open
+#[patch("/api/polls/{poll_id}")]
+pub async fn update_poll(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    req: web::Json<UpdatePollRequest>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("PATCH /api/polls/{}", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    if let Some(title) = &req.title {
+        validate_text("title", "Title", title, MAX_TITLE_LEN)?;
+    }
+    if let Some(description) = &req.description {
+        if description.chars().count() > MAX_DESCRIPTION_LEN {
+            return Err(AppError::Validation(vec![FieldError::new(
+                "description",
+                format!("Description must be at most {} characters.", MAX_DESCRIPTION_LEN),
+            )]));
+        }
+    }
+    // Some(None) clears a time, None leaves it alone
+    let opens_at = parse_optional_time("opens_at", &req.opens_at)?;
+    let closes_at = parse_optional_time("closes_at", &req.closes_at)?;
+
+    let mut tx = pool.begin().await?;
+    let status = lock_open_poll(&mut tx, poll_id).await?;
+
+    if opens_at.is_some() || closes_at.is_some() {
+        if opens_at.is_some() && !matches!(status, PollStatus::Draft | PollStatus::Scheduled) {
+            return Err(AppError::conflict(
+                "poll_already_open",
+                "The opening time can't change once the poll has opened.",
+            ));
+        }
+        let current = sqlx::query("SELECT opens_at, closes_at FROM polls WHERE id = ?")
+            .bind(poll_id)
+            .fetch_one(&mut tx)
+            .await?;
+        let opens_at = opens_at.unwrap_or_else(|| current.get("opens_at"));
+        let closes_at = closes_at.unwrap_or_else(|| current.get("closes_at"));
+        let errors = validate_schedule(opens_at, closes_at);
+        if !errors.is_empty() {
+            return Err(AppError::Validation(errors));
+        }
+        sqlx::query("UPDATE polls SET opens_at = ?, closes_at = ? WHERE id = ?")
+            .bind(opens_at)
+            .bind(closes_at)
+            .bind(poll_id)
+            .execute(&mut tx)
+            .await?;
+        // Clearing or moving back the opening time of a scheduled poll opens it now
+        let opens_later = matches!(opens_at, Some(opens_at) if opens_at > Utc::now().naive_utc());
+        if status == PollStatus::Scheduled && !opens_later {
+            transition_poll(&mut tx, poll_id, PollStatus::Open, Some(user.email.as_str()), None)
+                .await?;
+        }
+    }
+    if let Some(title) = &req.title {
+        sqlx::query("UPDATE polls SET title = ? WHERE id = ?")
+            .bind(title.trim())
+            .bind(poll_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    if let Some(description) = &req.description {
+        let description = Some(description.trim()).filter(|d| !d.is_empty());
+        sqlx::query("UPDATE polls SET description = ? WHERE id = ?")
+            .bind(description)
+            .bind(poll_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    if let Some(allow_vote_changes) = req.allow_vote_changes {
+        sqlx::query("UPDATE polls SET allow_vote_changes = ? WHERE id = ?")
+            .bind(allow_vote_changes)
+            .bind(poll_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    touch_poll(&mut tx, poll_id).await?;
+    tx.commit().await?;
+
+    notify_structure(&srv, poll_id).await;
+    Ok(HttpResponse::Ok().json("Poll updated."))
+}
+
+#[post("/api/polls/{poll_id}/questions")]
+pub async fn add_question(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    req: web::Json<AddQuestionRequest>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("POST /api/polls/{}/questions", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    validate_text("question_text", "Question text", &req.question_text, MAX_QUESTION_LEN)?;
+    let mut seen = HashSet::new();
+    for (i, option) in req.options.iter().enumerate() {
+        let field = format!("options[{}]", i);
+        validate_text(&field, "Option", option, MAX_OPTION_LEN)?;
+        if !seen.insert(option.trim().to_lowercase()) {
+            return Err(AppError::Validation(vec![FieldError::new(field, "Duplicate option.")]));
+        }
+    }
+    if req.spec.question_type.has_options() && (seen.len() < 2 || req.options.len() > MAX_OPTIONS) {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "options",
+            format!("A question needs between 2 and {} distinct options.", MAX_OPTIONS),
+        )]));
+    }
+    let errors = req.spec.validate("", req.options.len());
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    let spec = req.spec.clone().normalized();
+
+    let mut tx = pool.begin().await?;
+    lock_open_poll(&mut tx, poll_id).await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT COUNT(*) AS questions,
+               CAST(COALESCE(MAX(position), -1) + 1 AS SIGNED) AS next_position
+        FROM questions
+        WHERE poll_id = ?
+        "#,
+    )
+    .bind(poll_id)
+    .fetch_one(&mut tx)
+    .await?;
+    if row.get::<i64, _>("questions") as usize >= MAX_QUESTIONS {
+        return Err(AppError::conflict(
+            "too_many_questions",
+            format!("A poll can have at most {} questions.", MAX_QUESTIONS),
+        ));
+    }
+
+    let question_id = sqlx::query(
+        r#"
+        INSERT INTO questions
+            (poll_id, question_text, position, question_type,
+             min_selections, max_selections, scale_min, scale_max)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(poll_id)
+    .bind(req.question_text.trim())
+    .bind(row.get::<i64, _>("next_position"))
+    .bind(spec.question_type.as_str())
+    .bind(spec.min_selections)
+    .bind(spec.max_selections)
+    .bind(spec.scale_min)
+    .bind(spec.scale_max)
+    .execute(&mut tx)
+    .await?
+    .last_insert_id();
+
+    for (position, option) in req.options.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO poll_options (question_id, option_text, position) VALUES (?, ?, ?)",
+        )
+        .bind(question_id)
+        .bind(option.trim())
+        .bind(position as i32)
+        .execute(&mut tx)
+        .await?;
+    }
+    touch_poll(&mut tx, poll_id).await?;
+    tx.commit().await?;
+
+    notify_structure(&srv, poll_id).await;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "question_id": question_id })))
+}
+
+// Rewording a question is allowed even after votes, e.g. to fix a typo
+#[patch("/api/polls/{poll_id}/questions/{question_id}")]
+pub async fn update_question(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, i64)>,
+    req: web::Json<UpdateQuestionRequest>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, question_id) = path.into_inner();
+    let poll_id = parse_poll_id(&poll_id)?;
+    println!("PATCH /api/polls/{}/questions/{}", poll_id, question_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+    validate_text("question_text", "Question text", &req.question_text, MAX_QUESTION_LEN)?;
+
+    let mut tx = pool.begin().await?;
+    lock_open_poll(&mut tx, poll_id).await?;
+    ensure_question_in_poll(&mut tx, poll_id, question_id).await?;
+
+    sqlx::query("UPDATE questions SET question_text = ? WHERE id = ?")
+        .bind(req.question_text.trim())
+        .bind(question_id)
+        .execute(&mut tx)
+        .await?;
+    touch_poll(&mut tx, poll_id).await?;
+    tx.commit().await?;
+
+    notify_structure(&srv, poll_id).await;
+    Ok(HttpResponse::Ok().json("Question updated."))
+}
+
+// A question that has received votes can't be removed; reset the poll first
+#[delete("/api/polls/{poll_id}/questions/{question_id}")]
+pub async fn remove_question(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, i64)>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, question_id) = path.into_inner();
+    let poll_id = parse_poll_id(&poll_id)?;
+    println!("DELETE /api/polls/{}/questions/{}", poll_id, question_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+    lock_open_poll(&mut tx, poll_id).await?;
+    ensure_question_in_poll(&mut tx, poll_id, question_id).await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT
+            EXISTS(SELECT 1 FROM votes WHERE question_id = ?) AS has_votes,
+            (SELECT COUNT(*) FROM questions WHERE poll_id = ?) AS questions
+        "#,
+    )
+    .bind(question_id)
+    .bind(poll_id)
+    .fetch_one(&mut tx)
+    .await?;
+    if row.get::<bool, _>("has_votes") {
+        return Err(AppError::conflict(
+            "question_has_votes",
+            "This question already has votes and can't be removed.",
+        ));
+    }
+    if row.get::<i64, _>("questions") <= 1 {
+        return Err(AppError::conflict(
+            "last_question",
+            "A poll needs at least one question.",
+        ));
+    }
+
+    sqlx::query("DELETE FROM questions WHERE id = ?")
+        .bind(question_id)
+        .execute(&mut tx)
+        .await?;
+    touch_poll(&mut tx, poll_id).await?;
+    tx.commit().await?;
+
+    notify_structure(&srv, poll_id).await;
+    Ok(HttpResponse::Ok().json("Question removed."))
+}
+
+#[put("/api/polls/{poll_id}/questions/order")]
+pub async fn reorder_questions(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    req: web::Json<ReorderRequest>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("PUT /api/polls/{}/questions/order", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+    lock_open_poll(&mut tx, poll_id).await?;
+
+    let existing: Vec<i64> = sqlx::query("SELECT id FROM questions WHERE poll_id = ?")
+        .bind(poll_id)
+        .fetch_all(&mut tx)
+        .await?
+        .iter()
+        .map(|row| row.get("id"))
+        .collect();
+    check_permutation(&req.ids, &existing)?;
+
+    for (position, question_id) in req.ids.iter().enumerate() {
+        sqlx::query("UPDATE questions SET position = ? WHERE id = ?")
+            .bind(position as i32)
+            .bind(question_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    touch_poll(&mut tx, poll_id).await?;
+    tx.commit().await?;
+
+    notify_structure(&srv, poll_id).await;
+    Ok(HttpResponse::Ok().json("Questions reordered."))
+}
+
+#[post("/api/polls/{poll_id}/questions/{question_id}/options")]
+pub async fn add_option(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, i64)>,
+    req: web::Json<OptionRequest>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, question_id) = path.into_inner();
+    let poll_id = parse_poll_id(&poll_id)?;
+    println!("POST /api/polls/{}/questions/{}/options", poll_id, question_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+    validate_text("option_text", "Option", &req.option_text, MAX_OPTION_LEN)?;
+
+    let mut tx = pool.begin().await?;
+    lock_open_poll(&mut tx, poll_id).await?;
+    let spec = ensure_question_in_poll(&mut tx, poll_id, question_id).await?;
+    if !spec.question_type.has_options() {
+        return Err(AppError::conflict(
+            "question_has_no_options",
+            "Rating and free text questions have no options.",
+        ));
+    }
+
+    let existing = sqlx::query("SELECT option_text, position FROM poll_options WHERE question_id = ?")
+        .bind(question_id)
+        .fetch_all(&mut tx)
+        .await?;
+    if existing.len() >= MAX_OPTIONS {
+        return Err(AppError::conflict(
+            "too_many_options",
+            format!("A question can have at most {} options.", MAX_OPTIONS),
+        ));
+    }
+    let option_text = req.option_text.trim();
+    if existing
+        .iter()
+        .any(|row| row.get::<String, _>("option_text").to_lowercase() == option_text.to_lowercase())
+    {
+        return Err(AppError::conflict("duplicate_option", "This option already exists."));
+    }
+    let next_position = existing
+        .iter()
+        .map(|row| row.get::<i32, _>("position"))
+        .max()
+        .map_or(0, |position| position + 1);
+
+    let option_id = sqlx::query(
+        "INSERT INTO poll_options (question_id, option_text, position) VALUES (?, ?, ?)",
+    )
+    .bind(question_id)
+    .bind(option_text)
+    .bind(next_position)
+    .execute(&mut tx)
+    .await?
+    .last_insert_id();
+    touch_poll(&mut tx, poll_id).await?;
+    tx.commit().await?;
+
+    notify_structure(&srv, poll_id).await;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "option_id": option_id })))
+}
+
+// Renaming an option that has votes would change what people voted for
+#[patch("/api/polls/{poll_id}/questions/{question_id}/options/{option_id}")]
+pub async fn update_option(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, i64, i64)>,
+    req: web::Json<OptionRequest>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, question_id, option_id) = path.into_inner();
+    let poll_id = parse_poll_id(&poll_id)?;
+    println!(
+        "PATCH /api/polls/{}/questions/{}/options/{}",
+        poll_id, question_id, option_id
+    );
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+    validate_text("option_text", "Option", &req.option_text, MAX_OPTION_LEN)?;
+
+    let mut tx = pool.begin().await?;
+    lock_open_poll(&mut tx, poll_id).await?;
+    ensure_question_in_poll(&mut tx, poll_id, question_id).await?;
+    if option_votes(&mut tx, question_id, option_id).await? > 0 {
+        return Err(AppError::conflict(
+            "option_has_votes",
+            "This option already has votes and can't be changed.",
+        ));
+    }
+
+    let option_text = req.option_text.trim();
+    let duplicate = sqlx::query(
+        "SELECT 1 FROM poll_options WHERE question_id = ? AND id <> ? AND LOWER(option_text) = LOWER(?)",
+    )
+    .bind(question_id)
+    .bind(option_id)
+    .bind(option_text)
+    .fetch_optional(&mut tx)
+    .await?
+    .is_some();
+    if duplicate {
+        return Err(AppError::conflict("duplicate_option", "This option already exists."));
+    }
+
+    sqlx::query("UPDATE poll_options SET option_text = ? WHERE id = ?")
+        .bind(option_text)
+        .bind(option_id)
+        .execute(&mut tx)
+        .await?;
+    touch_poll(&mut tx, poll_id).await?;
+    tx.commit().await?;
+
+    notify_structure(&srv, poll_id).await;
+    Ok(HttpResponse::Ok().json("Option updated."))
+}
+
+#[delete("/api/polls/{poll_id}/questions/{question_id}/options/{option_id}")]
+pub async fn remove_option(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, i64, i64)>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, question_id, option_id) = path.into_inner();
+    let poll_id = parse_poll_id(&poll_id)?;
+    println!(
+        "DELETE /api/polls/{}/questions/{}/options/{}",
+        poll_id, question_id, option_id
+    );
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+    lock_open_poll(&mut tx, poll_id).await?;
+    let spec = ensure_question_in_poll(&mut tx, poll_id, question_id).await?;
+    if option_votes(&mut tx, question_id, option_id).await? > 0 {
+        return Err(AppError::conflict(
+            "option_has_votes",
+            "This option already has votes and can't be removed.",
+        ));
+    }
+
+    let options: i64 = sqlx::query("SELECT COUNT(*) AS options FROM poll_options WHERE question_id = ?")
+        .bind(question_id)
+        .fetch_one(&mut tx)
+        .await?
+        .get("options");
+    // Keep enough options for the question's minimum selection
+    let min_options = spec.min_selections.unwrap_or(0).max(2) as i64;
+    if options <= min_options {
+        return Err(AppError::conflict(
+            "too_few_options",
+            format!("This question needs at least {} options.", min_options),
+        ));
+    }
+
+    sqlx::query("DELETE FROM poll_options WHERE id = ?")
+        .bind(option_id)
+        .execute(&mut tx)
+        .await?;
+    touch_poll(&mut tx, poll_id).await?;
+    tx.commit().await?;
+
+    notify_structure(&srv, poll_id).await;
+    Ok(HttpResponse::Ok().json("Option removed."))
+}
+
+#[put("/api/polls/{poll_id}/questions/{question_id}/options/order")]
+pub async fn reorder_options(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, i64)>,
+    req: web::Json<ReorderRequest>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, question_id) = path.into_inner();
+    let poll_id = parse_poll_id(&poll_id)?;
+    println!("PUT /api/polls/{}/questions/{}/options/order", poll_id, question_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+    lock_open_poll(&mut tx, poll_id).await?;
+    ensure_question_in_poll(&mut tx, poll_id, question_id).await?;
+
+    let existing: Vec<i64> = sqlx::query("SELECT id FROM poll_options WHERE question_id = ?")
+        .bind(question_id)
+        .fetch_all(&mut tx)
+        .await?
+        .iter()
+        .map(|row| row.get("id"))
+        .collect();
+    check_permutation(&req.ids, &existing)?;
+
+    for (position, option_id) in req.ids.iter().enumerate() {
+        sqlx::query("UPDATE poll_options SET position = ? WHERE id = ?")
+            .bind(position as i32)
+            .bind(option_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    touch_poll(&mut tx, poll_id).await?;
+    tx.commit().await?;
+
+    notify_structure(&srv, poll_id).await;
+    Ok(HttpResponse::Ok().json("Options reordered."))
+}
@@ -1,6 +1,10 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{get, web, HttpResponse};
 use serde::{Deserialize, Serialize};
-use sqlx::{MySql, Pool};
+use sqlx::{MySql, Pool, Row};
+
+use crate::config::AuthUser;
+use crate::errors::AppError;
+use crate::polls::{bind_visible_poll, require_org_role, OrgRole, PollStatus, VISIBLE_POLL};
 
 #[derive(Serialize)]
 struct PollListResponse {
@@ -10,91 +14,108 @@ struct PollListResponse {
     creator_email: String,
     created_at: String,
     closed: bool,
+    organization_id: Option<i64>,
+    visibility: String,
+    status: String,
+    archived_at: Option<String>,
+    opens_at: Option<String>,
+    closes_at: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct PollStatusQuery {
     closed: Option<bool>, // Optional query param, defaults if not provided
     creator: Option<String>,
+    // List this organization's polls instead of the public ones
+    organization_id: Option<i64>,
+    archived: Option<bool>, // List archived polls instead, defaults to false
+    status: Option<PollStatus>, // Only polls in this status; closed and archived are ignored
 }
 
 #[get("/api/polls")]
 pub async fn get_polls(
     pool: web::Data<Pool<MySql>>,
     query: web::Query<PollStatusQuery>,
-) -> impl Responder {
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
     let closed_value = query.closed.clone().unwrap_or(false);
     println!("/GET polls?status={}", closed_value);
-    // Fetch polls based on the closed value
-    // if crateor is provided, fetch polls created by the creator
-    if let Some(creator) = &query.creator {
-        let polls_result = sqlx::query!(
-            r#"
-            SELECT id, title, description, creator_email, created_at, closed
-            FROM polls
-            WHERE closed = ? AND creator_email = ?
-            order by created_at desc
-            "#,
-            closed_value,
-            creator
-        )
-        .fetch_all(pool.as_ref())
-        .await;
-
-        match polls_result {
-            Ok(polls) => {
-                let poll_list: Vec<PollListResponse> = polls
-                    .iter()
-                    .map(|poll| PollListResponse {
-                        id: poll.id as i64,
-                        title: poll.title.clone(),
-                        description: poll.description.clone(),
-                        creator_email: poll.creator_email.clone(),
-                        created_at: poll
-                            .created_at
-                            .map_or_else(|| "".to_string(), |dt| dt.to_string()),
-                        closed: poll.closed.unwrap_or(0) != 0,
-                    })
-                    .collect();
 
-                return HttpResponse::Ok().json(poll_list);
-            }
-            Err(_) => return HttpResponse::InternalServerError().json("Failed to fetch polls."),
-        }
+    // Organization polls are only listed for members (and site admins)
+    if let Some(organization_id) = query.organization_id {
+        require_org_role(&user, organization_id, OrgRole::Member, &pool).await?;
     }
-    let polls_result = sqlx::query!(
+
+    // Which statuses to list: the one asked for, else archived or closed polls,
+    // and by default every poll that isn't closed
+    let statuses = match (query.status, query.archived.unwrap_or(false), closed_value) {
+        (Some(status), _, _) => vec![status],
+        (None, true, _) => vec![PollStatus::Archived],
+        (None, false, true) => vec![PollStatus::Closed],
+        (None, false, false) => vec![
+            PollStatus::Draft,
+            PollStatus::Scheduled,
+            PollStatus::Open,
+            PollStatus::Paused,
+        ],
+    };
+
+    // if creator is provided, fetch polls created by the creator
+    // Only polls the caller may see are listed, by the same rule as `poll_role`
+    let sql = format!(
         r#"
-        SELECT id, title, description, creator_email, created_at, closed
-        FROM polls
-        WHERE closed = ?
+        SELECT id, title, description, creator_email,
+               CAST(created_at AS CHAR) AS created_at, organization_id, visibility, status,
+               status IN ('closed', 'archived') AS closed,
+               CAST(opens_at AS CHAR) AS opens_at, CAST(closes_at AS CHAR) AS closes_at,
+               CAST(IF(status = 'archived',
+                       (SELECT MAX(created_at) FROM poll_status_history
+                        WHERE poll_id = p.id AND to_status = 'archived'),
+                       NULL) AS CHAR) AS archived_at
+        FROM polls p
+        WHERE status IN ({})
+          AND ((? IS NULL AND organization_id IS NULL) OR organization_id = ?)
+          AND (? IS NULL OR creator_email = ?)
+          AND (status <> 'draft' OR creator_email = ?)
+          AND {}
         order by created_at desc
         "#,
-        closed_value
-    )
-    .fetch_all(pool.as_ref())
-    .await;
-
-    match polls_result {
-        Ok(polls) => {
-            let poll_list: Vec<PollListResponse> = polls
-                .iter()
-                .map(|poll| PollListResponse {
-                    id: poll.id as i64,
-                    title: poll.title.clone(),
-                    description: poll.description.clone(),
-                    creator_email: poll.creator_email.clone(),
-                    created_at: poll
-                        .created_at
-                        .map_or_else(|| "".to_string(), |dt| dt.to_string()),
-                    closed: poll.closed.unwrap_or(0) != 0,
-                })
-                .collect();
-
-            HttpResponse::Ok().json(poll_list)
-        }
-        Err(_) => HttpResponse::InternalServerError().json("Failed to fetch polls."),
+        vec!["?"; statuses.len()].join(", "),
+        VISIBLE_POLL
+    );
+    let mut polls = sqlx::query(&sql);
+    for status in &statuses {
+        polls = polls.bind(status.as_str());
     }
-}
-
+    let polls = polls
+        .bind(query.organization_id)
+        .bind(query.organization_id)
+        .bind(&query.creator)
+        .bind(&query.creator)
+        .bind(&user.email);
+    let polls = bind_visible_poll(polls, &user.email)
+        .fetch_all(pool.as_ref())
+        .await?;
 
+    let poll_list: Vec<PollListResponse> = polls
+        .iter()
+        .map(|poll| PollListResponse {
+            id: poll.get("id"),
+            title: poll.get("title"),
+            description: poll.get("description"),
+            creator_email: poll.get("creator_email"),
+            created_at: poll
+                .get::<Option<String>, _>("created_at")
+                .unwrap_or_default(),
+            closed: poll.get("closed"),
+            organization_id: poll.get("organization_id"),
+            visibility: poll.get("visibility"),
+            status: poll.get("status"),
+            archived_at: poll.get("archived_at"),
+            opens_at: poll.get("opens_at"),
+            closes_at: poll.get("closes_at"),
+        })
+        .collect();
 
+    Ok(HttpResponse::Ok().json(poll_list))
+}
@@ -1,6 +1,11 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{get, web, HttpResponse};
 use serde::{Deserialize, Serialize};
 use sqlx::{MySql, Pool, Row};
+
+use crate::config::MaybeAuthUser;
+use crate::errors::AppError;
+use crate::polls::{require_poll_access, PollRole, PollStatus, QuestionSpec, ShareToken};
+
 #[derive(Serialize, Deserialize)]
 struct PollOption {
     id: i64,
@@ -12,7 +17,12 @@ struct PollOption {
 struct Question {
     id: i64,
     question_text: String,
+    #[serde(flatten)]
+    spec: QuestionSpec, // question_type and its limits
     options: Vec<PollOption>,
+    responses: i64, // Ballots cast on this question
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rating_average: Option<f64>, // Rating questions only
 }
 
 #[derive(Serialize, Deserialize)]
@@ -21,91 +31,138 @@ struct PollResponse {
     title: String,
     description: Option<String>,
     creator_email: String, // Changed from user ID to email
+    organization_id: Option<i64>,
+    visibility: String,
+    status: PollStatus,
+    archived_at: Option<String>,
+    allow_vote_changes: bool,
+    opens_at: Option<String>, // Votes are refused outside this window
+    closes_at: Option<String>,
     created_at: String,    // You may want to use a DateTime type
     questions: Vec<Question>,
     closed: bool,
 }
 
 #[get("/api/polls/{poll_id}")]
-pub async fn get_poll(pool: web::Data<Pool<MySql>>, path: web::Path<(String)>) -> impl Responder {
+pub async fn get_poll(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String)>,
+    user: MaybeAuthUser,
+    share: ShareToken,
+) -> Result<HttpResponse, AppError> {
     let (poll_id) = path.into_inner();
     println!("GET /api/polls/{poll_id}");
+    let poll_id: i64 = poll_id
+        .parse()
+        .map_err(|_| AppError::bad_request("invalid_poll_id", "Invalid poll id."))?;
+
+    // Public polls can be read anonymously; the rest answer 404 unless the caller
+    // may see them (organization member, invitee, share link holder, ...)
+    require_poll_access(user.0.as_ref(), share.as_deref(), poll_id, PollRole::Viewer, &pool)
+        .await?;
 
-    let poll_details = sqlx::query!(
+    let poll = sqlx::query(
         r#"
-        SELECT id, title, description, creator_email, created_at , closed
-        FROM polls
+        SELECT id, title, description, creator_email,
+               CAST(created_at AS CHAR) AS created_at, organization_id,
+               visibility, status, allow_vote_changes,
+               CAST(opens_at AS CHAR) AS opens_at, CAST(closes_at AS CHAR) AS closes_at,
+               CAST(IF(status = 'archived',
+                       (SELECT MAX(created_at) FROM poll_status_history
+                        WHERE poll_id = p.id AND to_status = 'archived'),
+                       NULL) AS CHAR) AS archived_at
+        FROM polls p
         WHERE id = ?
         "#,
-        poll_id
     )
-    .fetch_one(pool.as_ref())
-    .await;
+    .bind(poll_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::not_found("poll_not_found", "Poll not found."))?;
+
+    // Drafts stay hidden from everyone but their editors
+    let status: PollStatus = poll.get::<String, _>("status").parse()?;
+    if status == PollStatus::Draft
+        && require_poll_access(user.0.as_ref(), share.as_deref(), poll_id, PollRole::Editor, &pool)
+            .await
+            .is_err()
+    {
+        return Err(AppError::not_found("poll_not_found", "Poll not found."));
+    }
 
-    match poll_details {
-        Ok(poll) => {
-            let questions = sqlx::query(
-                r#"
-                SELECT id, question_text
-                FROM questions
-                WHERE poll_id = ?
-                "#,
-            )
-            .bind(poll_id)
-            .fetch_all(pool.as_ref())
-            .await;
+    let question_rows = sqlx::query(
+        r#"
+        SELECT q.id, q.question_text, q.question_type, q.min_selections, q.max_selections,
+               q.scale_min, q.scale_max,
+               (SELECT COUNT(*) FROM votes v WHERE v.question_id = q.id) AS responses,
+               (SELECT CAST(SUM(v.rating) AS SIGNED) FROM votes v WHERE v.question_id = q.id)
+                   AS rating_sum
+        FROM questions q
+        WHERE q.poll_id = ?
+        ORDER BY q.position, q.id
+        "#,
+    )
+    .bind(&poll_id)
+    .fetch_all(pool.as_ref())
+    .await?;
 
-            match questions {
-                Ok(question_rows) => {
-                    let mut question_vec = Vec::new();
-                    for question in question_rows {
-                        let options = sqlx::query(
-                            r#"
-                            SELECT id, option_text, score
-                            FROM poll_options
-                            WHERE question_id = ?
-                            "#,
-                        )
-                        .bind(question.get::<i64, _>("id"))
-                        .fetch_all(pool.as_ref())
-                        .await;
-                        let options_result = options.map(|option_rows| {
-                            option_rows
-                                .iter()
-                                .map(|option| PollOption {
-                                    id: option.get("id"),
-                                    option_text: option.get::<String, _>("option_text").clone(),
-                                    score: option.get("score"),
-                                })
-                                .collect::<Vec<PollOption>>()
-                        });
+    let mut question_vec = Vec::new();
+    for question in question_rows {
+        let option_rows = sqlx::query(
+            r#"
+            SELECT id, option_text, score
+            FROM poll_options
+            WHERE question_id = ?
+            ORDER BY position, id
+            "#,
+        )
+        .bind(question.get::<i64, _>("id"))
+        .fetch_all(pool.as_ref())
+        .await?;
 
-                        if let Ok(option_vec) = options_result {
-                            question_vec.push(Question {
-                                id: question.get("id"),
-                                question_text: question.get::<String, _>("question_text").clone(),
-                                options: option_vec,
-                            });
-                        }
-                    }
+        let option_vec = option_rows
+            .iter()
+            .map(|option| PollOption {
+                id: option.get("id"),
+                option_text: option.get::<String, _>("option_text").clone(),
+                score: option.get("score"),
+            })
+            .collect::<Vec<PollOption>>();
 
-                    let poll_response = PollResponse {
-                        id: poll.id as i64,
-                        title: poll.title,
-                        description: poll.description,
-                        creator_email: poll.creator_email,
-                        created_at: poll
-                            .created_at
-                            .map_or_else(|| "".to_string(), |dt| dt.to_string()),
-                        questions: question_vec,
-                        closed: poll.closed.unwrap_or(0) != 0,
-                    };
+        let responses: i64 = question.get("responses");
+        let rating_average = question
+            .get::<Option<i64>, _>("rating_sum")
+            .filter(|_| responses > 0)
+            .map(|sum| sum as f64 / responses as f64);
 
-                    return HttpResponse::Ok().json(poll_response);
-                }
-                Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
-            }
-        }
-        Err(err) => HttpResponse::NotFound().json(err.to_string()),
+        question_vec.push(Question {
+            id: question.get("id"),
+            question_text: question.get::<String, _>("question_text").clone(),
+            spec: QuestionSpec::from_row(&question)?,
+            options: option_vec,
+            responses,
+            rating_average,
+        });
     }
+
+    let poll_response = PollResponse {
+        id: poll.get("id"),
+        title: poll.get("title"),
+        description: poll.get("description"),
+        creator_email: poll.get("creator_email"),
+        organization_id: poll.get("organization_id"),
+        visibility: poll.get("visibility"),
+        status,
+        archived_at: poll.get("archived_at"),
+        allow_vote_changes: poll.get("allow_vote_changes"),
+        opens_at: poll.get("opens_at"),
+        closes_at: poll.get("closes_at"),
+        created_at: poll
+            .get::<Option<String>, _>("created_at")
+            .unwrap_or_default(),
+        questions: question_vec,
+        closed: status.is_closed(),
+    };
+
+    Ok(HttpResponse::Ok().json(poll_response))
 }
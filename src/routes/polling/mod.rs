@@ -1,8 +1,15 @@
+use crate::errors::AppError;
+
 pub mod check_attempted;
 pub mod close_poll;
 pub mod create_poll;
+pub mod delete_poll;
+pub mod edit_poll;
 pub mod get_polls;
 pub mod get_quiz;
+pub mod poll_access;
+pub mod poll_roles;
+pub mod poll_status;
 pub mod question_scores;
 pub mod reset_poll;
 pub mod vote_handler;
@@ -10,8 +17,20 @@ pub mod vote_handler;
 pub use check_attempted::*;
 pub use close_poll::*;
 pub use create_poll::*;
+pub use delete_poll::*;
+pub use edit_poll::*;
 pub use get_polls::*;
 pub use get_quiz::*;
+pub use poll_access::*;
+pub use poll_roles::*;
+pub use poll_status::*;
 pub use question_scores::*;
 pub use reset_poll::*;
 pub use vote_handler::*;
+
+// Poll ids arrive as path segments; anything but an integer is a bad request
+pub(crate) fn parse_poll_id(poll_id: &str) -> Result<i64, AppError> {
+    poll_id
+        .parse()
+        .map_err(|_| AppError::bad_request("invalid_poll_id", "Invalid poll id."))
+}
@@ -0,0 +1,193 @@
+use actix_web::{
+    delete, get, post, put,
+    web::{self},
+    HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool, Row};
+use uuid::Uuid;
+
+use super::parse_poll_id;
+use crate::config::AuthUser;
+use crate::errors::{is_duplicate_entry, AppError, FieldError};
+use crate::hash_token;
+use crate::polls::{require_poll_role, PollRole, Visibility};
+
+#[derive(Deserialize)]
+struct SetVisibilityRequest {
+    visibility: Visibility,
+}
+
+#[derive(Deserialize)]
+struct CreatePollInviteRequest {
+    email: String,
+}
+
+#[derive(Serialize)]
+struct PollInviteResponse {
+    email: String,
+    invited_by: String,
+    created_at: Option<String>,
+}
+
+// Secret for an unlisted poll's share link; two v4 UUIDs give 244 random bits
+pub fn generate_share_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+// Change who can see the poll. Making it unlisted issues a fresh share link,
+// any other mode drops the old one.
+#[put("/api/polls/{poll_id}/visibility")]
+pub async fn set_poll_visibility(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    req: web::Json<SetVisibilityRequest>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("PUT /api/polls/{}/visibility", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let share_token = (req.visibility == Visibility::Unlisted).then(generate_share_token);
+    sqlx::query("UPDATE polls SET visibility = ?, share_token_hash = ? WHERE id = ?")
+        .bind(req.visibility.as_str())
+        .bind(share_token.as_deref().map(hash_token))
+        .bind(poll_id)
+        .execute(pool.as_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "visibility": req.visibility,
+        "share_token": share_token,
+    })))
+}
+
+// Replace an unlisted poll's share link, e.g. after it leaked; the old link stops working
+#[post("/api/polls/{poll_id}/share-link")]
+pub async fn rotate_share_link(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("POST /api/polls/{}/share-link", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let share_token = generate_share_token();
+    let updated = sqlx::query(
+        "UPDATE polls SET share_token_hash = ? WHERE id = ? AND visibility = 'unlisted'",
+    )
+    .bind(hash_token(&share_token))
+    .bind(poll_id)
+    .execute(pool.as_ref())
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Err(AppError::conflict(
+            "poll_not_unlisted",
+            "Only unlisted polls have a share link.",
+        ));
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "share_token": share_token })))
+}
+
+// The invite list of an invite-only poll
+#[get("/api/polls/{poll_id}/invites")]
+pub async fn get_poll_invites(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("GET /api/polls/{}/invites", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT email, invited_by, CAST(created_at AS CHAR) AS created_at
+        FROM poll_invites
+        WHERE poll_id = ?
+        ORDER BY email
+        "#,
+    )
+    .bind(poll_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let invites: Vec<PollInviteResponse> = rows
+        .iter()
+        .map(|row| PollInviteResponse {
+            email: row.get("email"),
+            invited_by: row.get("invited_by"),
+            created_at: row.get("created_at"),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(invites))
+}
+
+#[post("/api/polls/{poll_id}/invites")]
+pub async fn create_poll_invite(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    req: web::Json<CreatePollInviteRequest>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("POST /api/polls/{}/invites", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let email = req.email.trim();
+    if email.is_empty() || !email.contains('@') {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "email",
+            "A valid email address is required.",
+        )]));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO poll_invites (poll_id, email, invited_by, created_at)
+        VALUES (?, ?, ?, UTC_TIMESTAMP())
+        "#,
+    )
+    .bind(poll_id)
+    .bind(email)
+    .bind(&user.email)
+    .execute(pool.as_ref())
+    .await
+    .map_err(|err| {
+        if is_duplicate_entry(&err) {
+            AppError::conflict("already_invited", "This user is already invited.")
+        } else {
+            AppError::from(err)
+        }
+    })?;
+
+    Ok(HttpResponse::Created().json("Invite added."))
+}
+
+#[delete("/api/polls/{poll_id}/invites/{email}")]
+pub async fn remove_poll_invite(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, String)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, email) = path.into_inner();
+    let poll_id = parse_poll_id(&poll_id)?;
+    println!("DELETE /api/polls/{}/invites/{}", poll_id, email);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let removed = sqlx::query("DELETE FROM poll_invites WHERE poll_id = ? AND email = ?")
+        .bind(poll_id)
+        .bind(&email)
+        .execute(pool.as_ref())
+        .await?
+        .rows_affected();
+    if removed == 0 {
+        return Err(AppError::not_found("invite_not_found", "Invite not found."));
+    }
+
+    Ok(HttpResponse::Ok().json("Invite removed."))
+}
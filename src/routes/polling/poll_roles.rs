@@ -0,0 +1,146 @@
+use actix_web::{
+    delete, get, put,
+    web::{self},
+    HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool, Row};
+
+use super::parse_poll_id;
+use crate::config::AuthUser;
+use crate::errors::AppError;
+use crate::polls::{require_poll_role, PollRole};
+
+#[derive(Deserialize)]
+struct SetPollRoleRequest {
+    role: String,
+}
+
+#[derive(Serialize)]
+struct PollRoleResponse {
+    email: String,
+    role: String,
+    granted_by: Option<String>,
+    created_at: Option<String>,
+}
+
+// Everyone with an explicit role on the poll; other users are voters
+#[get("/api/polls/{poll_id}/roles")]
+pub async fn get_poll_roles(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("GET /api/polls/{}/roles", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT user_email, role, granted_by, CAST(created_at AS CHAR) AS created_at
+        FROM poll_roles
+        WHERE poll_id = ?
+        ORDER BY FIELD(role, 'owner', 'editor', 'voter', 'viewer'), user_email
+        "#,
+    )
+    .bind(poll_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let roles: Vec<PollRoleResponse> = rows
+        .iter()
+        .map(|row| PollRoleResponse {
+            email: row.get("user_email"),
+            role: row.get("role"),
+            granted_by: row.get("granted_by"),
+            created_at: row.get("created_at"),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+// Give a user the editor, voter or viewer role on the poll. Only the owner
+// (or an admin) shares a poll, and ownership itself can't be handed out here.
+#[put("/api/polls/{poll_id}/roles/{email}")]
+pub async fn set_poll_role(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, String)>,
+    req: web::Json<SetPollRoleRequest>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, email) = path.into_inner();
+    let poll_id = parse_poll_id(&poll_id)?;
+    println!("PUT /api/polls/{}/roles/{}", poll_id, email);
+    require_poll_role(&user, poll_id, PollRole::Owner, &pool).await?;
+
+    let role: PollRole = req.role.parse()?;
+    if role == PollRole::Owner {
+        return Err(AppError::bad_request(
+            "invalid_role",
+            "A poll has a single owner; grant editor to share management.",
+        ));
+    }
+
+    // A poll always keeps its owner
+    let current = sqlx::query_scalar::<_, String>(
+        "SELECT role FROM poll_roles WHERE poll_id = ? AND user_email = ?",
+    )
+    .bind(poll_id)
+    .bind(&email)
+    .fetch_optional(pool.as_ref())
+    .await?;
+    if current.as_deref() == Some(PollRole::Owner.as_str()) {
+        return Err(AppError::conflict(
+            "cannot_change_owner",
+            "The poll owner's role can't be changed.",
+        ));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO poll_roles (poll_id, user_email, role, granted_by, created_at)
+        VALUES (?, ?, ?, ?, UTC_TIMESTAMP())
+        ON DUPLICATE KEY UPDATE
+            role = VALUES(role),
+            granted_by = VALUES(granted_by)
+        "#,
+    )
+    .bind(poll_id)
+    .bind(&email)
+    .bind(role.as_str())
+    .bind(&user.email)
+    .execute(pool.as_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "email": email, "role": role })))
+}
+
+// Remove a user's explicit role, making them a plain voter again
+#[delete("/api/polls/{poll_id}/roles/{email}")]
+pub async fn remove_poll_role(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, String)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, email) = path.into_inner();
+    let poll_id = parse_poll_id(&poll_id)?;
+    println!("DELETE /api/polls/{}/roles/{}", poll_id, email);
+    require_poll_role(&user, poll_id, PollRole::Owner, &pool).await?;
+
+    let removed = sqlx::query(
+        "DELETE FROM poll_roles WHERE poll_id = ? AND user_email = ? AND role <> 'owner'",
+    )
+    .bind(poll_id)
+    .bind(&email)
+    .execute(pool.as_ref())
+    .await?;
+    if removed.rows_affected() == 0 {
+        return Err(AppError::not_found(
+            "role_not_found",
+            "No removable role for this user on the poll.",
+        ));
+    }
+
+    Ok(HttpResponse::Ok().json("Role removed."))
+}
@@ -0,0 +1,115 @@
+use actix::Addr;
+use actix_web::{
+    get, post,
+    web::{self, Data},
+    HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool, Row};
+
+use super::parse_poll_id;
+use crate::config::AuthUser;
+use crate::errors::{AppError, FieldError};
+use crate::polls::{
+    lock_poll_status, require_poll_role, transition_poll, PollRole, PollStatus,
+    MAX_STATUS_REASON_LEN,
+};
+use crate::{Lobby, NotifyPollId};
+
+#[derive(Deserialize)]
+struct SetPollStatusRequest {
+    status: String,
+    reason: Option<String>, // Kept in the status history
+}
+
+#[derive(Serialize)]
+struct StatusChangeResponse {
+    from_status: Option<String>, // None for the status the poll was created in
+    to_status: String,
+    changed_by: Option<String>, // None when the scheduler made the change
+    reason: Option<String>,
+    created_at: Option<String>,
+}
+
+// Move a poll through its lifecycle: publish or schedule a draft, pause and
+// resume voting, close or reopen it. Editors manage those; archiving and
+// restoring stay with the owner.
+#[post("/api/polls/{poll_id}/status")]
+pub async fn set_poll_status(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    req: web::Json<SetPollStatusRequest>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("POST /api/polls/{}/status", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let to: PollStatus = req.status.parse()?;
+    let reason = req.reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    if matches!(reason, Some(reason) if reason.chars().count() > MAX_STATUS_REASON_LEN) {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "reason",
+            format!("Reason must be at most {} characters.", MAX_STATUS_REASON_LEN),
+        )]));
+    }
+
+    // Lock the row first so the status the owner check is based on is the one
+    // the transition starts from
+    let mut tx = pool.begin().await?;
+    let current = lock_poll_status(&mut tx, poll_id).await?;
+    if to == PollStatus::Archived || current == PollStatus::Archived {
+        require_poll_role(&user, poll_id, PollRole::Owner, &pool).await?;
+    }
+    let from = transition_poll(&mut tx, poll_id, to, Some(user.email.as_str()), reason).await?;
+    tx.commit().await?;
+
+    if let Err(e) = srv.send(NotifyPollId { poll_id }).await {
+        eprintln!("Error sending message to lobby: {:?}", e);
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "poll_id": poll_id,
+        "from": from,
+        "to": to,
+    })))
+}
+
+// Every status the poll went through, oldest first
+#[get("/api/polls/{poll_id}/status/history")]
+pub async fn get_poll_status_history(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<String>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let poll_id = parse_poll_id(&path.into_inner())?;
+    println!("GET /api/polls/{}/status/history", poll_id);
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT from_status, to_status, changed_by, reason,
+               CAST(created_at AS CHAR) AS created_at
+        FROM poll_status_history
+        WHERE poll_id = ?
+        ORDER BY id
+        "#,
+    )
+    .bind(poll_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let history: Vec<StatusChangeResponse> = rows
+        .iter()
+        .map(|row| StatusChangeResponse {
+            from_status: row.get("from_status"),
+            to_status: row.get("to_status"),
+            changed_by: row.get("changed_by"),
+            reason: row.get("reason"),
+            created_at: row.get("created_at"),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(history))
+}
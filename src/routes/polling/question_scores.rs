@@ -1,9 +1,14 @@
-use std::option;
-
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{get, web, HttpResponse};
 use serde::Serialize;
 use sqlx::{MySql, Pool, Row};
 
+use crate::config::{AuthUser, MaybeAuthUser};
+use crate::errors::AppError;
+use crate::polls::{
+    borda_count, instant_runoff, load_question, require_poll_access, require_poll_role, BordaScore,
+    PollRole, QuestionType, RunoffResult, ShareToken,
+};
+
 #[derive(Serialize)]
 struct OptionScore {
     id: i64,
@@ -22,44 +27,188 @@ struct QuestionScoresResponse {
 pub async fn get_question_scores(
     pool: web::Data<Pool<MySql>>,
     path: web::Path<(String, String)>,
-) -> impl Responder {
+    user: MaybeAuthUser,
+    share: ShareToken,
+) -> Result<HttpResponse, AppError> {
     let (poll_id, question_id) = path.into_inner();
     println!(
         "GET /api/polls/{}/questions/{}/scores",
         poll_id, question_id
     );
 
-    // Fetch the options and their scores for the specified question
-    let options = sqlx::query(
+    let poll_id: i64 = poll_id
+        .parse()
+        .map_err(|_| AppError::bad_request("invalid_poll_id", "Invalid poll id."))?;
+    require_poll_access(user.0.as_ref(), share.as_deref(), poll_id, PollRole::Viewer, &pool)
+        .await?;
+
+    // Fetch the options and their scores for the specified question; the join
+    // keeps a visible poll's id from unlocking another poll's question
+    let option_rows = sqlx::query(
         r#"
-        SELECT id, option_text, score
-        FROM poll_options
-        WHERE question_id = ?
+        SELECT po.id, po.option_text, po.score
+        FROM poll_options po
+        INNER JOIN questions q ON q.id = po.question_id
+        WHERE po.question_id = ? AND q.poll_id = ?
+        ORDER BY po.position, po.id
         "#,
     )
     .bind(question_id.clone())
+    .bind(poll_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let option_scores: Vec<OptionScore> = option_rows
+        .iter()
+        .map(|option| OptionScore {
+            id: option.get("id"),
+            option_text: option.get("option_text"),
+            score: option.get("score"),
+        })
+        .collect();
+
+    // Construct the response
+    let response = QuestionScoresResponse {
+        question_id,
+        options: option_scores,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[derive(Serialize)]
+struct AnswerResponse {
+    answer: String,
+    created_at: Option<String>,
+}
+
+// Free text answers of a question, without who gave them
+#[get("/api/polls/{poll_id}/questions/{question_id}/answers")]
+pub async fn get_question_answers(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, i64)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, question_id) = path.into_inner();
+    println!("GET /api/polls/{}/questions/{}/answers", poll_id, question_id);
+    let poll_id: i64 = poll_id
+        .parse()
+        .map_err(|_| AppError::bad_request("invalid_poll_id", "Invalid poll id."))?;
+    require_poll_role(&user, poll_id, PollRole::Editor, &pool).await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT v.answer_text, CAST(v.created_at AS CHAR) AS created_at
+        FROM votes v
+        INNER JOIN questions q ON q.id = v.question_id
+        WHERE v.question_id = ? AND q.poll_id = ? AND v.answer_text IS NOT NULL
+        ORDER BY v.created_at, v.id
+        "#,
+    )
+    .bind(question_id)
+    .bind(poll_id)
     .fetch_all(pool.as_ref())
-    .await;
-
-    match options {
-        Ok(option_rows) => {
-            let option_scores: Vec<OptionScore> = option_rows
-                .iter()
-                .map(|option| OptionScore {
-                    id: option.get("id"),
-                    option_text: option.get("option_text"),
-                    score: option.get("score"),
-                })
-                .collect();
-
-            // Construct the response
-            let response = QuestionScoresResponse {
-                question_id,
-                options: option_scores,
-            };
-
-            HttpResponse::Ok().json(response)
+    .await?;
+
+    let answers: Vec<AnswerResponse> = rows
+        .iter()
+        .map(|row| AnswerResponse {
+            answer: row.get("answer_text"),
+            created_at: row.get("created_at"),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(answers))
+}
+
+#[derive(Serialize)]
+struct ResultOption {
+    id: i64,
+    option_text: String,
+}
+
+#[derive(Serialize)]
+struct QuestionResultsResponse {
+    question_id: i64,
+    ballots: usize,
+    options: Vec<ResultOption>,
+    instant_runoff: RunoffResult,
+    borda: Vec<BordaScore>,
+}
+
+// Full tally of a ranked question: instant-runoff rounds and Borda points
+#[get("/api/polls/{poll_id}/questions/{question_id}/results")]
+pub async fn get_question_results(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, i64)>,
+    user: MaybeAuthUser,
+    share: ShareToken,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, question_id) = path.into_inner();
+    println!("GET /api/polls/{}/questions/{}/results", poll_id, question_id);
+    let poll_id: i64 = poll_id
+        .parse()
+        .map_err(|_| AppError::bad_request("invalid_poll_id", "Invalid poll id."))?;
+    require_poll_access(user.0.as_ref(), share.as_deref(), poll_id, PollRole::Viewer, &pool)
+        .await?;
+
+    let mut conn = pool.acquire().await?;
+    let (spec, _) = load_question(&mut conn, poll_id, question_id).await?;
+    if spec.question_type != QuestionType::Ranked {
+        return Err(AppError::bad_request(
+            "question_not_ranked",
+            "Only ranked questions have runoff results; use the scores route instead.",
+        ));
+    }
+
+    let options: Vec<ResultOption> = sqlx::query(
+        "SELECT id, option_text FROM poll_options WHERE question_id = ? ORDER BY position, id",
+    )
+    .bind(question_id)
+    .fetch_all(&mut conn)
+    .await?
+    .iter()
+    .map(|row| ResultOption {
+        id: row.get("id"),
+        option_text: row.get("option_text"),
+    })
+    .collect();
+
+    // Every ballot's options in order of preference
+    let selections = sqlx::query(
+        r#"
+        SELECT vs.vote_id, vs.option_id
+        FROM vote_selections vs
+        INNER JOIN votes v ON v.id = vs.vote_id
+        WHERE v.question_id = ?
+        ORDER BY vs.vote_id, vs.`rank`
+        "#,
+    )
+    .bind(question_id)
+    .fetch_all(&mut conn)
+    .await?;
+
+    let mut ballots: Vec<Vec<i64>> = Vec::new();
+    let mut current_vote = None;
+    for row in &selections {
+        let vote_id: i64 = row.get("vote_id");
+        if current_vote != Some(vote_id) {
+            current_vote = Some(vote_id);
+            ballots.push(Vec::new());
+        }
+        if let Some(ballot) = ballots.last_mut() {
+            ballot.push(row.get("option_id"));
         }
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
     }
+
+    let option_ids: Vec<i64> = options.iter().map(|option| option.id).collect();
+    let response = QuestionResultsResponse {
+        question_id,
+        ballots: ballots.len(),
+        instant_runoff: instant_runoff(&option_ids, &ballots),
+        borda: borda_count(&option_ids, &ballots),
+        options,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
 }
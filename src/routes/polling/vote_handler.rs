@@ -1,182 +1,374 @@
 use actix::Addr;
 use actix_web::{
-    post,
+    delete, post,
     web::{self, Data},
-    HttpRequest, HttpResponse, Responder,
+    HttpRequest, HttpResponse,
+};
+use sqlx::mysql::MySqlRow;
+use sqlx::{MySql, Pool, Row, Transaction};
+use crate::config::{decode_vote_token, AuthUser, Settings};
+use crate::errors::{is_duplicate_entry, AppError};
+use crate::polls::{
+    load_question, require_poll_access, Ballot, PollRole, PollStatus, QuestionSpec, QuestionType,
+    ShareToken,
 };
-use sqlx::{MySql, Pool, Row};
-use serde::{Deserialize, Serialize};
-use jsonwebtoken::{decode, DecodingKey, Validation}; // Add dependencies for JWT decoding
 use crate::{Lobby, NotifyPollId};
 
-// Define your claims structure
-#[derive(Debug, Serialize, Deserialize)]
-struct Claims {
-    sub: String,       // user email
-    option_id: String, // option ID
-    exp: usize,        // expiration time
-}
-
-
 #[post("/api/polls/{poll_id}/vote")]
 pub async fn crate_vote(
     pool: web::Data<Pool<MySql>>,
     path: web::Path<(String)>,
     req: HttpRequest,
     srv: Data<Addr<Lobby>>,
-) -> impl Responder {
-    let poll_id: i64 = path.into_inner().parse().unwrap();
+    settings: Data<Settings>,
+    user: AuthUser,
+    share: ShareToken,
+) -> Result<HttpResponse, AppError> {
+    let poll_id: i64 = path
+        .into_inner()
+        .parse()
+        .map_err(|_| AppError::bad_request("invalid_poll_id", "Invalid poll id."))?;
     println!("POST /api/polls/{}/vote", poll_id);
 
     // Get the token from the authorization header
     let token = match req.headers().get("Authentication") {
         Some(header_value) => header_value.to_str().unwrap_or("").to_string(),
-        None => return HttpResponse::BadRequest().json("Missing token"),
+        None => return Err(AppError::bad_request("missing_vote_token", "Missing token.")),
     };
 
     // Remove "Bearer " from the token if present
     let token = token.strip_prefix("Bearer ").unwrap_or(&token);
 
-    // Decode the token to get claims
-    let secret_key = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-    let validation = Validation::default();
-    let decoded_token = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret_key.as_ref()),
-        &validation,
-    );
-
-    let my_claims = match decoded_token {
-        Ok(c) => c.claims,
-        Err(_) => return HttpResponse::Unauthorized().json("Invalid token"),
-    };
+    // Decode the token to get claims; expiry, issuer and audience are checked here
+    let my_claims = decode_vote_token(&settings.jwt, token)?;
+
+    // The token is only good for the poll and the user it was minted for
+    if my_claims.poll_id != poll_id {
+        return Err(AppError::forbidden(
+            "vote_token_wrong_poll",
+            "Vote token was issued for a different poll.",
+        ));
+    }
+    if my_claims.sub != user.email {
+        return Err(AppError::forbidden(
+            "vote_token_wrong_user",
+            "Vote token was issued to a different user.",
+        ));
+    }
+
+    // Viewers may follow a poll but not vote on it; unlisted polls also need the share link
+    require_poll_access(Some(&user), share.as_deref(), poll_id, PollRole::Voter, &pool).await?;
 
-    // Now you can use my_claims.sub and my_claims.option_id in your logic
     let user_id = my_claims.sub;
-    let option_id = my_claims.option_id;
+    let question_id = my_claims.question_id;
 
-    // Check if the poll exists and is open
-    let poll_exists = sqlx::query!(
+    // Every check and write below runs in one transaction so concurrent
+    // requests cannot both pass the checks and double count
+    let mut tx = pool.begin().await?;
+
+    // Spend the token first; its primary key makes a replay fail here
+    sqlx::query(
         r#"
-        SELECT closed FROM polls WHERE id = ?
+        INSERT INTO used_vote_tokens (jti, user_email, poll_id)
+        VALUES (?, ?, ?)
         "#,
-        poll_id
     )
-    .fetch_one(pool.get_ref())
+    .bind(&my_claims.jti)
+    .bind(&user_id)
+    .bind(poll_id)
+    .execute(&mut tx)
     .await
-    .unwrap();
-
-    if poll_exists.closed == Some(1) {
-        return HttpResponse::BadRequest().json("Poll is closed.");
-    }
+    .map_err(|err| {
+        if is_duplicate_entry(&err) {
+            AppError::conflict("vote_token_replayed", "Vote token has already been used.")
+        } else {
+            AppError::from(err)
+        }
+    })?;
 
-    // Check if the question with that option exists and check if user already voted
-    let question_exists = sqlx::query!(
+    // Check if the poll exists and is open; the shared lock keeps it from
+    // being closed underneath us until the vote is committed
+    let poll = sqlx::query(
         r#"
-        SELECT COUNT(*) as count
-        FROM poll_options
-        WHERE id = ? AND question_id IN (
-            SELECT id FROM questions WHERE poll_id = ?
-        )
+        SELECT status, allow_vote_changes,
+               (opens_at IS NOT NULL AND opens_at > UTC_TIMESTAMP()) AS not_open_yet,
+               (closes_at IS NOT NULL AND closes_at <= UTC_TIMESTAMP()) AS past_close
+        FROM polls
+        WHERE id = ?
+        LOCK IN SHARE MODE
         "#,
-        option_id,
-        poll_id
     )
-    .fetch_one(pool.get_ref())
-    .await
-    .unwrap();
+    .bind(poll_id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| AppError::not_found("poll_not_found", "Poll not found."))?;
 
-    let count: i64 = question_exists.count;
+    ensure_poll_open(&poll)?;
 
-    if count == 0 {
-        return HttpResponse::BadRequest().json("Invalid option for this poll.");
-    }
+    // The question may have been edited since the token was issued, so check
+    // the ballot again against its current options
+    let (spec, option_ids) = load_question(&mut tx, poll_id, question_id).await?;
+    let ballot = spec.check_ballot(&my_claims.ballot, &option_ids)?;
 
-    let already_voted = sqlx::query!(
-        r#"
-        SELECT COUNT(*) as count
-        FROM votes
-        WHERE question_id = (
-            SELECT question_id FROM poll_options WHERE id = ?
-        ) AND user_email = ?
-        "#,
-        option_id,
-        user_id
-    )
-    .fetch_one(pool.get_ref())
-    .await
-    .unwrap();
+    let single_option = match spec.question_type {
+        QuestionType::Single => ballot.option_ids.first().copied(),
+        _ => None,
+    };
+
+    // A second ballot replaces the first when the poll allows it
+    let existing = find_vote(&mut tx, question_id, &user_id).await?;
+    let action = match existing {
+        Some(vote_id) => {
+            if !poll.get::<bool, _>("allow_vote_changes") {
+                return Err(AppError::conflict(
+                    "already_voted",
+                    "User has already voted for this question.",
+                ));
+            }
+            let previous = load_ballot(&mut tx, vote_id).await?;
+            adjust_scores(&mut tx, spec.scored_options(&previous), -1).await?;
+            sqlx::query("DELETE FROM vote_selections WHERE vote_id = ?")
+                .bind(vote_id)
+                .execute(&mut tx)
+                .await?;
+            sqlx::query(
+                r#"
+                UPDATE votes
+                SET option_id = ?, rating = ?, answer_text = ?, updated_at = UTC_TIMESTAMP()
+                WHERE id = ?
+                "#,
+            )
+            .bind(single_option)
+            .bind(ballot.rating)
+            .bind(&ballot.text)
+            .bind(vote_id)
+            .execute(&mut tx)
+            .await?;
+            insert_selections(&mut tx, vote_id, &spec, &ballot).await?;
+            "change"
+        }
+        None => {
+            // uq_votes_question_user rejects a concurrent first vote on the question
+            let vote_id = sqlx::query(
+                r#"
+                INSERT INTO votes (question_id, option_id, rating, answer_text, user_email)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(question_id)
+            .bind(single_option)
+            .bind(ballot.rating)
+            .bind(&ballot.text)
+            .bind(&user_id)
+            .execute(&mut tx)
+            .await
+            .map_err(|err| {
+                if is_duplicate_entry(&err) {
+                    AppError::conflict("already_voted", "User has already voted for this question.")
+                } else {
+                    AppError::from(err)
+                }
+            })?
+            .last_insert_id() as i64;
+            insert_selections(&mut tx, vote_id, &spec, &ballot).await?;
+            "cast"
+        }
+    };
+
+    // Update the score in the poll_options table
+    adjust_scores(&mut tx, spec.scored_options(&ballot), 1).await?;
+    record_vote_history(&mut tx, question_id, &user_id, action, Some(&ballot)).await?;
 
-    let count: i64 = already_voted.count;
+    tx.commit().await?;
 
-    if count > 0 {
-        return HttpResponse::BadRequest().json("User has already voted for this question.");
+    // Notify the lobby of the vote
+    if let Err(e) = srv.send(NotifyPollId { poll_id }).await {
+        eprintln!("Error sending message to lobby: {:?}", e);
     }
 
-    // Check if the user has already voted for this option
-    let already_voted = sqlx::query(
+    let message = if action == "change" { "Vote changed" } else { "Vote created" };
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": message
+    })))
+}
+
+// Withdraw the caller's ballot on a question, if the poll allows vote changes
+#[delete("/api/polls/{poll_id}/questions/{question_id}/vote")]
+pub async fn retract_vote(
+    pool: web::Data<Pool<MySql>>,
+    path: web::Path<(String, i64)>,
+    srv: Data<Addr<Lobby>>,
+    user: AuthUser,
+    share: ShareToken,
+) -> Result<HttpResponse, AppError> {
+    let (poll_id, question_id) = path.into_inner();
+    let poll_id: i64 = poll_id
+        .parse()
+        .map_err(|_| AppError::bad_request("invalid_poll_id", "Invalid poll id."))?;
+    println!("DELETE /api/polls/{}/questions/{}/vote", poll_id, question_id);
+    require_poll_access(Some(&user), share.as_deref(), poll_id, PollRole::Voter, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+
+    let poll = sqlx::query(
         r#"
-        SELECT COUNT(*) as count
-        FROM votes 
-        WHERE option_id = ? AND user_email = ?
+        SELECT status, allow_vote_changes,
+               (opens_at IS NOT NULL AND opens_at > UTC_TIMESTAMP()) AS not_open_yet,
+               (closes_at IS NOT NULL AND closes_at <= UTC_TIMESTAMP()) AS past_close
+        FROM polls
+        WHERE id = ?
+        LOCK IN SHARE MODE
         "#,
     )
-    .bind(&option_id)
-    .bind(user_id.clone())
-    .fetch_one(pool.get_ref())
-    .await
-    .unwrap();
+    .bind(poll_id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| AppError::not_found("poll_not_found", "Poll not found."))?;
+    ensure_poll_open(&poll)?;
+    if !poll.get::<bool, _>("allow_vote_changes") {
+        return Err(AppError::conflict(
+            "vote_changes_disabled",
+            "This poll doesn't allow changing votes.",
+        ));
+    }
+
+    let (spec, _) = load_question(&mut tx, poll_id, question_id).await?;
+    let vote_id = find_vote(&mut tx, question_id, &user.email)
+        .await?
+        .ok_or_else(|| AppError::not_found("vote_not_found", "You haven't voted on this question."))?;
 
-    let count: i64 = already_voted.get("count");
+    let previous = load_ballot(&mut tx, vote_id).await?;
+    adjust_scores(&mut tx, spec.scored_options(&previous), -1).await?;
+    // vote_selections rows go with the vote
+    sqlx::query("DELETE FROM votes WHERE id = ?")
+        .bind(vote_id)
+        .execute(&mut tx)
+        .await?;
+    record_vote_history(&mut tx, question_id, &user.email, "retract", None).await?;
 
-    if count > 0 {
-        return HttpResponse::BadRequest().json("User has already voted for this option.");
+    tx.commit().await?;
+
+    if let Err(e) = srv.send(NotifyPollId { poll_id }).await {
+        eprintln!("Error sending message to lobby: {:?}", e);
     }
 
-    // Insert the vote into the database
-    let _ = sqlx::query(
-        r#"
-        INSERT INTO votes (question_id, option_id, user_email)
-        VALUES (
-            (SELECT question_id FROM poll_options WHERE id = ?),
-            ?,
-            ?
-        )
-        "#,
-    )
-    .bind(&option_id)
-    .bind(&option_id)
-    .bind(user_id)
-    .execute(pool.get_ref())
-    .await
-    .unwrap();
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Vote retracted"
+    })))
+}
 
-    // Update the score in the poll_options table
-    let _ = sqlx::query(
-        r#"
-        UPDATE poll_options
-        SET score = score + 1
-        WHERE id = ?
-        "#,
+// Voting needs an open poll, or a scheduled one whose opens_at has passed, and
+// nothing past its closes_at, whether or not the scheduler has caught up yet.
+// `poll` must select status, not_open_yet and past_close.
+pub(crate) fn ensure_poll_open(poll: &MySqlRow) -> Result<(), AppError> {
+    let status: PollStatus = poll.get::<String, _>("status").parse()?;
+    if status.is_closed() || poll.get::<bool, _>("past_close") {
+        return Err(AppError::conflict("poll_closed", "Poll is closed."));
+    }
+    if status == PollStatus::Paused {
+        return Err(AppError::conflict("poll_paused", "Voting on this poll is paused."));
+    }
+    if status == PollStatus::Draft
+        || (status == PollStatus::Scheduled && poll.get::<bool, _>("not_open_yet"))
+    {
+        return Err(AppError::conflict("poll_not_open", "Poll is not open for voting yet."));
+    }
+    Ok(())
+}
+
+// The caller's vote on `question_id`, locked until the transaction ends
+async fn find_vote(
+    tx: &mut Transaction<'_, MySql>,
+    question_id: i64,
+    user_email: &str,
+) -> Result<Option<i64>, AppError> {
+    let vote = sqlx::query("SELECT id FROM votes WHERE question_id = ? AND user_email = ? FOR UPDATE")
+        .bind(question_id)
+        .bind(user_email)
+        .fetch_optional(&mut *tx)
+        .await?;
+    Ok(vote.map(|row| row.get("id")))
+}
+
+// Rebuild the ballot stored for `vote_id`
+async fn load_ballot(tx: &mut Transaction<'_, MySql>, vote_id: i64) -> Result<Ballot, AppError> {
+    let vote = sqlx::query("SELECT rating, answer_text FROM votes WHERE id = ?")
+        .bind(vote_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let option_ids = sqlx::query(
+        "SELECT option_id FROM vote_selections WHERE vote_id = ? ORDER BY `rank`, option_id",
     )
-    .bind(&option_id)
-    .execute(pool.get_ref())
-    .await
-    .unwrap();
+    .bind(vote_id)
+    .fetch_all(&mut *tx)
+    .await?
+    .iter()
+    .map(|row| row.get("option_id"))
+    .collect();
 
-    // Notify the lobby of the vote
-    srv.send(NotifyPollId {
-        poll_id: poll_id.clone(),
+    Ok(Ballot {
+        option_ids,
+        rating: vote.get("rating"),
+        text: vote.get("answer_text"),
     })
-    .await
-    .map_err(|e| {
-        eprintln!("Error sending message to lobby: {:?}", e);
-        actix_web::error::ErrorInternalServerError(e)
-    });
+}
 
-    HttpResponse::Ok().json(serde_json::json!({
-        "message": "Vote created"
-    }))
+// Record every picked option, with its preference on ranked ballots
+async fn insert_selections(
+    tx: &mut Transaction<'_, MySql>,
+    vote_id: i64,
+    spec: &QuestionSpec,
+    ballot: &Ballot,
+) -> Result<(), AppError> {
+    for (i, option_id) in ballot.option_ids.iter().enumerate() {
+        let rank = (spec.question_type == QuestionType::Ranked).then(|| i as i32 + 1);
+        sqlx::query("INSERT INTO vote_selections (vote_id, option_id, `rank`) VALUES (?, ?, ?)")
+            .bind(vote_id)
+            .bind(option_id)
+            .bind(rank)
+            .execute(&mut *tx)
+            .await?;
+    }
+    Ok(())
 }
 
+async fn adjust_scores(
+    tx: &mut Transaction<'_, MySql>,
+    option_ids: &[i64],
+    delta: i64,
+) -> Result<(), AppError> {
+    for option_id in option_ids {
+        sqlx::query("UPDATE poll_options SET score = score + ? WHERE id = ?")
+            .bind(delta)
+            .bind(option_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    Ok(())
+}
 
+async fn record_vote_history(
+    tx: &mut Transaction<'_, MySql>,
+    question_id: i64,
+    user_email: &str,
+    action: &str,
+    ballot: Option<&Ballot>,
+) -> Result<(), AppError> {
+    let ballot = ballot
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|err| AppError::internal(format!("Failed to serialize ballot: {}", err)))?;
+    sqlx::query(
+        r#"
+        INSERT INTO vote_history (question_id, user_email, action, ballot, created_at)
+        VALUES (?, ?, ?, ?, UTC_TIMESTAMP())
+        "#,
+    )
+    .bind(question_id)
+    .bind(user_email)
+    .bind(action)
+    .bind(ballot)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}